@@ -1,7 +1,7 @@
 local core = require "core"
 local command = require "core.command"
 local common = require "core.common"
-local config = require "core.config"
+local style = require "core.style"
 local translate = require "core.doc.translate"
 local DocView = require "core.docview"
 
@@ -17,10 +17,45 @@ end
 
 
 local function get_indent_string()
-  if config.tab_type == "hard" then
+  local indent_type, indent_size = doc():get_indent_info()
+  if indent_type == "hard" then
     return "\t"
   end
-  return string.rep(" ", config.indent_size)
+  return string.rep(" ", indent_size)
+end
+
+
+local html_escapes = { ["&"] = "&amp;", ["<"] = "&lt;", [">"] = "&gt;" }
+
+-- Returns the selected text as syntax highlighted HTML.
+local function get_selection_html()
+  local line1, col1, line2, col2 = doc():get_selection(true)
+  local parts = { "<pre>" }
+  for line = line1, line2 do
+    local col = 1
+    for _, type, text in doc().highlighter:each_token(line) do
+      if line == line2 and col >= col2 then break end
+      local first = line == line1 and math.max(col1 - col + 1, 1) or 1
+      local last = line == line2 and col2 - col or #text
+      local part = text:sub(first, last)
+      if part ~= "" then
+        local color = style.syntax[type] or style.syntax.normal
+        table.insert(parts, string.format("<span style=\"color:#%02x%02x%02x\">%s</span>",
+          color[1], color[2], color[3], (part:gsub("[&<>]", html_escapes))))
+      end
+      col = col + #text
+    end
+  end
+  table.insert(parts, "</pre>")
+  return table.concat(parts)
+end
+
+
+local function copy_selection()
+  local text = doc():get_text(doc():get_selection())
+  local ok, err = system.set_clipboard(text, { html = get_selection_html() })
+  if not ok then core.error("Could not copy: %s", err) end
+  return ok
 end
 
 
@@ -58,8 +93,12 @@ end
 
 
 local function save(filename)
-  doc():save(filename)
-  core.log("Saved \"%s\"", doc().filename)
+  if doc():save(filename) then
+    core.error("Saved \"%s\" in place as its directory is not writable, " ..
+      "it could have been left partially written", doc().filename)
+  else
+    core.log("Saved \"%s\"", doc().filename)
+  end
 end
 
 
@@ -74,21 +113,23 @@ local commands = {
 
   ["doc:cut"] = function()
     if doc():has_selection() then
-      local text = doc():get_text(doc():get_selection())
-      system.set_clipboard(text)
-      doc():delete_to(0)
+      if copy_selection() then
+        doc():delete_to(0)
+      end
     end
   end,
 
   ["doc:copy"] = function()
     if doc():has_selection() then
-      local text = doc():get_text(doc():get_selection())
-      system.set_clipboard(text)
+      copy_selection()
     end
   end,
 
   ["doc:paste"] = function()
-    doc():text_input(system.get_clipboard():gsub("\r", ""))
+    local text = system.get_clipboard()
+    if text then
+      doc():text_input(text:gsub("\r", ""))
+    end
   end,
 
   ["doc:newline"] = function()
@@ -126,8 +167,9 @@ local commands = {
     local line, col = doc():get_selection()
     if not doc():has_selection() then
       local text = doc():get_text(line, 1, line, col)
-      if #text >= config.indent_size and text:find("^ *$") then
-        doc():delete_to(0, -config.indent_size)
+      local _, indent_size = doc():get_indent_info()
+      if #text >= indent_size and text:find("^ *$") then
+        doc():delete_to(0, -indent_size)
         return
       end
     end
@@ -279,7 +321,8 @@ local commands = {
   end,
 
   ["doc:toggle-line-ending"] = function()
-    doc().crlf = not doc().crlf
+    doc().line_ending = doc().line_ending == "lf" and "crlf" or "lf"
+    doc().mixed_line_endings = false
   end,
 
   ["doc:save-as"] = function()
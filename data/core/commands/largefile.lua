@@ -0,0 +1,45 @@
+local core = require "core"
+local command = require "core.command"
+
+local last_text
+
+
+local function find(view, text)
+  view:find(text, true, function(found)
+    if not found then
+      core.error("Couldn't find %q", text)
+    end
+  end)
+end
+
+
+command.add("core.largefileview", {
+  ["large-file:find"] = function()
+    local view = core.active_view
+    core.command_view:set_text(last_text or "", true)
+    core.command_view:enter("Find Text", function(text)
+      last_text = text
+      find(view, text)
+    end)
+  end,
+
+  ["large-file:repeat-find"] = function()
+    if not last_text then
+      core.error("No find to continue from")
+    else
+      find(core.active_view, last_text)
+    end
+  end,
+
+  ["large-file:go-to-line"] = function()
+    local view = core.active_view
+    core.command_view:enter("Go To Line", function(text)
+      local line = tonumber(text)
+      if not line then
+        core.error("Invalid line number")
+        return
+      end
+      view:scroll_to_line(math.floor(line))
+    end)
+  end,
+})
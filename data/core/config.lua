@@ -6,6 +6,7 @@ config.max_log_items = 80
 config.message_timeout = 3
 config.mouse_wheel_scroll = 50 * SCALE
 config.file_size_limit = 10
+config.large_file_size = 50
 config.ignore_files = "^%."
 config.symbol_pattern = "[%a_][%w_]*"
 config.non_word_chars = " \t\n/\\()\"':,.;<>~!@#$%^&*|+=[]{}`?-"
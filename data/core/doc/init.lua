@@ -50,6 +50,8 @@ function Doc:reset()
   self.undo_stack = { idx = 1 }
   self.redo_stack = { idx = 1 }
   self.clean_change_id = 1
+  self.line_ending = "lf"
+  self.trailing_newline = true
   self.highlighter = Highlighter(self)
   self:reset_syntax()
 end
@@ -67,35 +69,63 @@ end
 
 function Doc:load(filename)
   local fp = assert( io.open(filename, "rb") )
+  local text = fp:read("*a")
+  fp:close()
   self:reset()
   self.filename = filename
+  local info = system.analyze_text(text)
+  self.line_ending = info.line_ending or "lf"
+  self.mixed_line_endings = info.mixed_line_endings
+  self.trailing_newline = info.trailing_newline
+  self.indent_type, self.indent_size = info.indent_type, info.indent_size
+  self.encoding, self.bom = system.detect_encoding(text)
+  if self.encoding ~= "UTF-8" or self.bom then
+    local converted = system.convert_encoding(text, self.encoding, "UTF-8")
+    if not converted then
+      -- the guess was wrong; every byte is a valid ISO-8859-1 character
+      self.encoding, self.bom = "ISO-8859-1", false
+      converted = assert( system.convert_encoding(text, self.encoding, "UTF-8") )
+    end
+    text = converted
+  end
   self.lines = {}
-  for line in fp:lines() do
-    if line:byte(-1) == 13 then
-      line = line:sub(1, -2)
-      self.crlf = true
+  for line in text:gsub("\r\n?", "\n"):gmatch("[^\n]*\n?") do
+    if line ~= "" then
+      table.insert(self.lines, line:gsub("\n$", "") .. "\n")
     end
-    table.insert(self.lines, line .. "\n")
   end
   if #self.lines == 0 then
     table.insert(self.lines, "\n")
   end
-  fp:close()
   self:reset_syntax()
 end
 
 
 function Doc:save(filename)
   filename = filename or assert(self.filename, "no filename set to default to")
-  local fp = assert( io.open(filename, "wb") )
-  for _, line in ipairs(self.lines) do
-    if self.crlf then line = line:gsub("\n", "\r\n") end
-    fp:write(line)
+  local text = table.concat(self.lines)
+  if not self.trailing_newline then text = text:gsub("\n$", "") end
+  if self.line_ending == "crlf" then
+    text = text:gsub("\n", "\r\n")
+  elseif self.line_ending == "cr" then
+    text = text:gsub("\n", "\r")
   end
-  fp:close()
+  if self.encoding and (self.encoding ~= "UTF-8" or self.bom) then
+    local opts = { bom = self.bom }
+    text = assert( system.convert_encoding(text, "UTF-8", self.encoding, opts) )
+  end
+  local _, in_place = assert( system.write_file_atomic(filename, text) )
   self.filename = filename or self.filename
   self:reset_syntax()
   self:clean()
+  return in_place
+end
+
+
+-- Returns the indentation used by the file, or the configured one when it
+-- could not be guessed.
+function Doc:get_indent_info()
+  return self.indent_type or config.tab_type, self.indent_size or config.indent_size
 end
 
 
@@ -95,6 +95,19 @@ function DocView:get_scrollable_size()
 end
 
 
+-- Only the visible lines are measured, as measuring all of them on each
+-- scroll would be slow with large docs.
+function DocView:get_h_scrollable_size()
+  local width = 0
+  local minline, maxline = self:get_visible_line_range()
+  for i = minline, maxline do
+    width = math.max(width, self:get_font():get_width(self.doc.lines[i]))
+  end
+  -- The same margin as when scrolling to the caret.
+  return self:get_gutter_width() + width + self.size.x / 5
+end
+
+
 function DocView:get_font()
   return style[self.font]
 end
@@ -137,25 +150,13 @@ end
 function DocView:get_col_x_offset(line, col)
   local text = self.doc.lines[line]
   if not text then return 0 end
-  return self:get_font():get_width(text:sub(1, col - 1))
+  return self:get_font():x_offset(text, col)
 end
 
 
 function DocView:get_x_offset_col(line, x)
   local text = self.doc.lines[line]
-
-  local xoffset, last_i, i = 0, 1, 1
-  for char in common.utf8_chars(text) do
-    local w = self:get_font():get_width(char)
-    if xoffset >= x then
-      return (xoffset - x > w / 2) and last_i or i
-    end
-    xoffset = xoffset + w
-    last_i = i
-    i = i + #char
-  end
-
-  return #text
+  return self:get_font():char_index_at(text, x)
 end
 
 
@@ -218,7 +219,15 @@ function DocView:on_mouse_pressed(button, x, y, clicks)
   if caught then
     return
   end
-  if keymap.modkeys["shift"] then
+  if button == "middle" then
+    -- paste the primary selection where clicked, as X11 applications do
+    local text = system.has_clipboard_text({ primary = true })
+      and system.get_clipboard({ primary = true })
+    if text then
+      self.doc:set_selection(self:resolve_screen_position(x, y))
+      self.doc:text_input(text:gsub("\r", ""))
+    end
+  elseif keymap.modkeys["shift"] then
     if clicks == 1 then
       local line1, col1 = select(3, self.doc:get_selection())
       local line2, col2 = self:resolve_screen_position(x, y)
@@ -253,6 +262,10 @@ end
 
 function DocView:on_mouse_released(button)
   DocView.super.on_mouse_released(self, button)
+  if self.mouse_selecting and self.doc:has_selection() then
+    local text = self.doc:get_text(self.doc:get_selection())
+    system.set_clipboard(text, { primary = true })
+  end
   self.mouse_selecting = nil
 end
 
@@ -31,17 +31,14 @@ local function project_scan_thread()
     coroutine.yield()
     t = t or {}
     local size_limit = config.file_size_limit * 10e5
-    local all = system.list_dir(path) or {}
+    local all = system.list_dir(path, { details = true }) or {}
     local dirs, files = {}, {}
 
-    for _, file in ipairs(all) do
-      if not common.match_pattern(file, config.ignore_files) then
-        local file = (path ~= "." and path .. PATHSEP or "") .. file
-        local info = system.get_file_info(file)
-        if info and info.size < size_limit then
-          info.filename = file
-          table.insert(info.type == "dir" and dirs or files, info)
-        end
+    for _, info in ipairs(all) do
+      if info.size and info.size < size_limit
+      and not common.match_pattern(info.name, config.ignore_files) then
+        info.filename = (path ~= "." and path .. PATHSEP or "") .. info.name
+        table.insert(info.type == "dir" and dirs or files, info)
       end
     end
 
@@ -117,7 +114,7 @@ function core.init()
   local got_project_error = not core.load_project_module()
 
   for _, filename in ipairs(files) do
-    core.root_view:open_doc(core.open_doc(filename))
+    core.open_file(filename)
   end
 
   if got_plugin_error or got_user_error or got_project_error then
@@ -266,6 +263,19 @@ function core.open_doc(filename)
 end
 
 
+-- Opens a file in a doc view, or in a read-only large file view if it is
+-- bigger than `config.large_file_size` megabytes.
+function core.open_file(filename)
+  local info = system.get_file_info(filename)
+  if info and info.type == "file"
+  and info.size > config.large_file_size * 10e5 then
+    core.log_quiet("Opened large file \"%s\"", filename)
+    return core.root_view:open_large_file(filename)
+  end
+  return core.root_view:open_doc(core.open_doc(filename))
+end
+
+
 function core.get_views_referencing_doc(doc)
   local res = {}
   local views = core.root_view.root_node:get_children()
@@ -344,12 +354,16 @@ function core.on_event(type, ...)
     if info and info.type == "dir" then
       system.exec(string.format("%q %q", EXEFILE, filename))
     else
-      local ok, doc = core.try(core.open_doc, filename)
-      if ok then
-        local node = core.root_view.root_node:get_child_overlapping_point(mx, my)
-        node:set_active_view(node.active_view)
-        core.root_view:open_doc(doc)
-      end
+      local node = core.root_view.root_node:get_child_overlapping_point(mx, my)
+      node:set_active_view(node.active_view)
+      core.try(core.open_file, filename)
+    end
+  elseif type == "textdropped" then
+    local text, mx, my = ...
+    local node = core.root_view.root_node:get_child_overlapping_point(mx, my)
+    if node.active_view.doc then
+      node:set_active_view(node.active_view)
+      node.active_view:on_text_input(text)
     end
   elseif type == "quit" then
     core.quit()
@@ -364,7 +378,7 @@ function core.step()
   local mouse_moved = false
   local mouse = { x = 0, y = 0, dx = 0, dy = 0 }
 
-  for type, a,b,c,d in system.poll_event do
+  for type, a,b,c,d,e in system.poll_event do
     if type == "mousemoved" then
       mouse_moved = true
       mouse.x, mouse.y = a, b
@@ -372,7 +386,7 @@ function core.step()
     elseif type == "textinput" and did_keymap then
       did_keymap = false
     else
-      local _, res = core.try(core.on_event, type, a, b, c, d)
+      local _, res = core.try(core.on_event, type, a, b, c, d, e)
       did_keymap = res or did_keymap
     end
     core.redraw = true
@@ -114,11 +114,11 @@ keymap.add {
   ["alt+8"] = "root:switch-to-tab-8",
   ["alt+9"] = "root:switch-to-tab-9",
 
-  ["ctrl+f"] = "find-replace:find",
+  ["ctrl+f"] = { "find-replace:find", "large-file:find" },
   ["ctrl+r"] = "find-replace:replace",
-  ["f3"] = "find-replace:repeat-find",
+  ["f3"] = { "find-replace:repeat-find", "large-file:repeat-find" },
   ["shift+f3"] = "find-replace:previous-find",
-  ["ctrl+g"] = "doc:go-to-line",
+  ["ctrl+g"] = { "doc:go-to-line", "large-file:go-to-line" },
   ["ctrl+s"] = "doc:save",
   ["ctrl+shift+s"] = "doc:save-as",
 
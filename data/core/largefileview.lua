@@ -0,0 +1,185 @@
+local core = require "core"
+local config = require "core.config"
+local style = require "core.style"
+local View = require "core.view"
+
+-- How many bytes are searched before letting the editor run.
+local search_step = 4 * 1024 * 1024
+
+-- A read-only view of a file too large to be loaded as a doc, its lines are
+-- read on demand from the file, which is indexed in the background.
+local LargeFileView = View:extend()
+
+
+function LargeFileView:new(filename)
+  LargeFileView.super.new(self)
+  self.cursor = "ibeam"
+  self.scrollable = true
+  self.filename = filename
+  self.file = assert(system.large_file.open(filename))
+  self.line_count, self.indexed = self.file:get_line_count()
+  self.found = nil
+end
+
+
+function LargeFileView:try_close(do_close)
+  self.closed = true
+  self.file:close()
+  do_close()
+end
+
+
+function LargeFileView:get_name()
+  local name = self.filename:match("[^/%\\]*$")
+  return self.indexed and name or name .. " (indexing)"
+end
+
+
+function LargeFileView:get_font()
+  return style.code_font
+end
+
+
+function LargeFileView:get_line_height()
+  return math.floor(self:get_font():get_height() * config.line_height)
+end
+
+
+function LargeFileView:get_gutter_width()
+  return self:get_font():get_width(self.line_count) + style.padding.x * 2
+end
+
+
+function LargeFileView:get_scrollable_size()
+  return self:get_line_height() * math.max(self.line_count - 1, 0) + self.size.y
+end
+
+
+function LargeFileView:get_h_scrollable_size()
+  local width = 0
+  local minline, maxline = self:get_visible_line_range()
+  for i = minline, maxline do
+    width = math.max(width, self:get_font():get_width(self:get_line_text(i)))
+  end
+  return self:get_gutter_width() + width + style.padding.x
+end
+
+
+function LargeFileView:get_line_text(idx)
+  local text = self.file:get_line(idx) or ""
+  return (text:gsub("\r$", ""))
+end
+
+
+function LargeFileView:get_visible_line_range()
+  local x, y, x2, y2 = self:get_content_bounds()
+  local lh = self:get_line_height()
+  local minline = math.max(1, math.floor(y / lh))
+  local maxline = math.min(self.line_count, math.floor(y2 / lh) + 1)
+  return minline, maxline
+end
+
+
+function LargeFileView:scroll_to_line(line)
+  local min, max = self:get_visible_line_range()
+  if line <= min or line >= max then
+    local lh = self:get_line_height()
+    self.scroll.to.y = math.max(0, lh * (line - 1) - self.size.y / 2)
+  end
+end
+
+
+-- Searches from a line and a column a few megabytes at a time, returns the
+-- position of the match if any.
+function LargeFileView:search(text, line, col, no_case)
+  local opt = { no_case = no_case, limit = search_step }
+  while not self.closed do
+    local line1, col1, col2 = self.file:find(text, line, col, opt)
+    if line1 then return line1, col1 end
+    if not col1 then return end
+    line, col = col1, col2
+    coroutine.yield()
+  end
+end
+
+
+-- Finds `text` after the last match in the background, starting over from
+-- the top of the file when there is none after it. `on_done` is called with
+-- whether it was found.
+function LargeFileView:find(text, no_case, on_done)
+  local line, col = 1, 1
+  if self.found then
+    line, col = self.found.line, self.found.col + 1
+  end
+  core.add_thread(function()
+    local line1, col1 = self:search(text, line, col, no_case)
+    if not line1 and line > 1 then
+      line1, col1 = self:search(text, 1, 1, no_case)
+    end
+    if self.closed then return end
+    if line1 then
+      self.found = { line = line1, col = col1, len = #text }
+      self:scroll_to_line(line1)
+      core.redraw = true
+    end
+    on_done(line1 ~= nil)
+  end, self)
+end
+
+
+function LargeFileView:update()
+  if not self.indexed then
+    self.line_count, self.indexed = self.file:get_line_count()
+    core.redraw = true
+  end
+  LargeFileView.super.update(self)
+end
+
+
+function LargeFileView:draw_line(idx, x, y)
+  local font = self:get_font()
+  local text = self:get_line_text(idx)
+  local found = self.found
+  if found and found.line == idx then
+    local x1 = x + font:x_offset(text, found.col)
+    local x2 = x + font:x_offset(text, found.col + found.len)
+    renderer.draw_rect(x1, y, x2 - x1, self:get_line_height(), style.selection)
+  end
+  local ty = y + (self:get_line_height() - font:get_height()) / 2
+  renderer.draw_text(font, text, x, ty, style.syntax["normal"])
+end
+
+
+function LargeFileView:draw()
+  self:draw_background(style.background)
+
+  local font = self:get_font()
+  font:set_tab_width(font:get_width(" ") * config.indent_size)
+
+  local minline, maxline = self:get_visible_line_range()
+  local lh = self:get_line_height()
+  local gw = self:get_gutter_width()
+  local ox, oy = self:get_content_offset()
+  local yoffset = (lh - font:get_height()) / 2
+
+  local y = oy + (minline - 1) * lh + style.padding.y
+  for i = minline, maxline do
+    local x = self.position.x + style.padding.x
+    renderer.draw_text(font, i, x, y + yoffset, style.line_number)
+    y = y + lh
+  end
+
+  local pos = self.position
+  core.push_clip_rect(pos.x + gw, pos.y, self.size.x, self.size.y)
+  y = oy + (minline - 1) * lh + style.padding.y
+  for i = minline, maxline do
+    self:draw_line(i, ox + gw, y)
+    y = y + lh
+  end
+  core.pop_clip_rect()
+
+  self:draw_scrollbar()
+end
+
+
+return LargeFileView
@@ -5,6 +5,7 @@ local keymap = require "core.keymap"
 local Object = require "core.object"
 local View = require "core.view"
 local DocView = require "core.docview"
+local LargeFileView = require "core.largefileview"
 
 
 local EmptyView = View:extend()
@@ -418,6 +419,28 @@ function RootView:open_doc(doc)
 end
 
 
+function RootView:open_large_file(filename)
+  local node = self:get_active_node()
+  if node.locked and core.last_active_view then
+    core.set_active_view(core.last_active_view)
+    node = self:get_active_node()
+  end
+  assert(not node.locked, "Cannot open file on locked node")
+  local abs_filename = system.absolute_path(filename)
+  for i, view in ipairs(node.views) do
+    if view:is(LargeFileView)
+    and system.absolute_path(view.filename) == abs_filename then
+      node:set_active_view(node.views[i])
+      return view
+    end
+  end
+  local view = LargeFileView(filename)
+  node:add_view(view)
+  self.root_node:update_layout()
+  return view
+end
+
+
 function RootView:on_mouse_pressed(button, x, y, clicks)
   local div = self.root_node:get_divider_overlapping_point(x, y)
   if div then
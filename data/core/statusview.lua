@@ -112,7 +112,9 @@ function StatusView:get_items()
       style.font, style.dim, self.separator2, style.text,
       #dv.doc.lines, " lines",
       self.separator,
-      dv.doc.crlf and "CRLF" or "LF"
+      dv.doc.encoding or "UTF-8", dv.doc.bom and " BOM" or "",
+      self.separator,
+      dv.doc.line_ending:upper(), dv.doc.mixed_line_endings and " (mixed)" or ""
     }
   end
 
@@ -48,6 +48,13 @@ function View:get_scrollable_size()
 end
 
 
+-- Returns the width of the content, views which do not scroll horizontally
+-- can leave it at 0.
+function View:get_h_scrollable_size()
+  return 0
+end
+
+
 function View:get_scrollbar_rect()
   local sz = self:get_scrollable_size()
   if sz <= self.size.y or sz == math.huge then
@@ -95,9 +102,13 @@ function View:on_text_input(text)
 end
 
 
-function View:on_mouse_wheel(y)
+function View:on_mouse_wheel(y, x)
   if self.scrollable then
     self.scroll.to.y = self.scroll.to.y + y * -config.mouse_wheel_scroll
+    if x and x ~= 0 then
+      local max = math.max(0, self:get_h_scrollable_size() - self.size.x)
+      self.scroll.to.x = common.clamp(self.scroll.to.x + x * config.mouse_wheel_scroll, 0, max)
+    end
   end
 end
 
@@ -95,10 +95,14 @@ function ResultsView:open_selected_result()
     return
   end
   core.try(function()
-    local dv = core.root_view:open_doc(core.open_doc(res.file))
+    local view = core.open_file(res.file)
     core.root_view.root_node:update_layout()
-    dv.doc:set_selection(res.line, res.col)
-    dv:scroll_to_line(res.line, false, true)
+    if view.doc then
+      view.doc:set_selection(res.line, res.col)
+      view:scroll_to_line(res.line, false, true)
+    else
+      view:scroll_to_line(res.line)
+    end
   end)
 end
 
@@ -0,0 +1,196 @@
+use crate::encoding::{self, Encoding};
+use std::borrow::Cow;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "lf",
+            Self::CrLf => "crlf",
+            Self::Cr => "cr",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) enum IndentType {
+    /// Indented with tabs.
+    Hard,
+    /// Indented with spaces.
+    Soft,
+}
+
+impl IndentType {
+    /// Returns the name used by the `tab_type` setting.
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Hard => "hard",
+            Self::Soft => "soft",
+        }
+    }
+}
+
+/// The conventions followed by some text.
+pub(super) struct TextInfo {
+    /// The most common line ending, `None` if there is no line break.
+    pub(super) line_ending: Option<LineEnding>,
+    pub(super) mixed_line_endings: bool,
+    pub(super) indent_type: Option<IndentType>,
+    /// The number of spaces of an indentation level, only guessed for soft
+    /// indentation.
+    pub(super) indent_size: Option<usize>,
+    pub(super) trailing_newline: bool,
+    pub(super) bom: bool,
+}
+
+/// Counts each kind of line ending, in the order of `LineEnding`.
+fn count_line_endings(text: &[u8]) -> [usize; 3] {
+    let mut counts = [0; 3];
+    let mut bytes = text.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\n' => counts[0] += 1,
+            b'\r' if bytes.peek() == Some(&&b'\n') => {
+                bytes.next();
+                counts[1] += 1;
+            }
+            b'\r' => counts[2] += 1,
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// Guesses the indentation from how much it grows between lines, so that
+/// alignment and comment continuations are mostly ignored.
+fn guess_indent(text: &[u8]) -> (Option<IndentType>, Option<usize>) {
+    let mut hard = 0;
+    let mut soft = 0;
+    // How many times the indentation grew by each number of spaces.
+    let mut steps = [0; 9];
+    let mut previous = 0;
+    for line in text.split(|&byte| byte == b'\n' || byte == b'\r') {
+        if line.iter().all(|byte| byte.is_ascii_whitespace()) {
+            continue;
+        }
+        if line[0] == b'\t' {
+            hard += 1;
+            previous = 0;
+            continue;
+        }
+        let spaces = line.iter().take_while(|&&byte| byte == b' ').count();
+        if spaces > previous && (2..steps.len()).contains(&(spaces - previous)) {
+            steps[spaces - previous] += 1;
+            soft += 1;
+        }
+        previous = spaces;
+    }
+    if hard > soft {
+        (Some(IndentType::Hard), None)
+    } else if soft > 0 {
+        // On a tie the smaller step wins, as larger ones are often made of
+        // several levels.
+        let size = (2..steps.len()).rev().max_by_key(|&size| steps[size]);
+        (Some(IndentType::Soft), size)
+    } else {
+        (None, None)
+    }
+}
+
+/// Finds the line ending, the indentation, and whether a byte order mark and
+/// a final newline are present in some text in any supported encoding.
+pub(super) fn analyze(data: &[u8]) -> TextInfo {
+    let (encoding, bom) = encoding::detect(data);
+    // The line breaks and the indentation of the other encodings are ASCII.
+    let text = match encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => match encoding::decode(data, encoding) {
+            Ok(text) => Cow::Owned(text.into_bytes()),
+            Err(_) => Cow::Borrowed(data),
+        },
+        _ if bom => Cow::Borrowed(&data[encoding.bom().len()..]),
+        _ => Cow::Borrowed(data),
+    };
+
+    let counts = count_line_endings(&text);
+    let endings = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr];
+    let line_ending = (0..endings.len())
+        .rev()
+        .filter(|&i| counts[i] > 0)
+        .max_by_key(|&i| counts[i])
+        .map(|i| endings[i]);
+    let (indent_type, indent_size) = guess_indent(&text);
+    TextInfo {
+        line_ending,
+        mixed_line_endings: counts.iter().filter(|&&count| count > 0).count() > 1,
+        indent_type,
+        indent_size,
+        trailing_newline: matches!(text.last(), Some(b'\n') | Some(b'\r')),
+        bom,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_line_endings() {
+        let info = analyze(b"a\r\nb\r\nc\nd\re\r\n");
+        assert_eq!(count_line_endings(b"a\r\nb\r\nc\nd\re\r\n"), [1, 3, 1]);
+        assert_eq!(info.line_ending, Some(LineEnding::CrLf));
+        assert!(info.mixed_line_endings);
+        assert!(info.trailing_newline);
+
+        let info = analyze(b"a\nb\n");
+        assert_eq!(info.line_ending, Some(LineEnding::Lf));
+        assert!(!info.mixed_line_endings);
+    }
+
+    #[test]
+    fn trailing_cr() {
+        let info = analyze(b"a\r");
+        assert_eq!(info.line_ending, Some(LineEnding::Cr));
+        assert!(info.trailing_newline);
+
+        // A CR at the very end is not the start of a CRLF.
+        assert_eq!(count_line_endings(b"a\r\nb\r"), [0, 1, 1]);
+        let info = analyze(b"a\nb\r");
+        assert_eq!(info.line_ending, Some(LineEnding::Lf));
+        assert!(info.mixed_line_endings);
+    }
+
+    #[test]
+    fn tabs_and_spaces() {
+        let info = analyze(b"a\n\tb\n\tc\nd\n  e\n");
+        assert_eq!(info.indent_type, Some(IndentType::Hard));
+        assert_eq!(info.indent_size, None);
+
+        let info = analyze(b"a\n    b\n        c\nd\n    e\n  \n");
+        assert_eq!(info.indent_type, Some(IndentType::Soft));
+        assert_eq!(info.indent_size, Some(4));
+    }
+
+    #[test]
+    fn smaller_step_on_tie() {
+        let info = analyze(b"a\n  b\nc\n    d\n");
+        assert_eq!(info.indent_type, Some(IndentType::Soft));
+        assert_eq!(info.indent_size, Some(2));
+    }
+
+    #[test]
+    fn empty_text() {
+        let info = analyze(b"");
+        assert_eq!(info.line_ending, None);
+        assert!(!info.mixed_line_endings);
+        assert_eq!(info.indent_type, None);
+        assert_eq!(info.indent_size, None);
+        assert!(!info.trailing_newline);
+        assert!(!info.bom);
+    }
+}
@@ -1,12 +1,19 @@
-use crate::c_str;
+use crate::{c_str, os_string_from_bytes};
 use lua_sys::*;
 use renderer::luaopen_renderer;
-use std::os::raw::c_int;
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    os::raw::{c_char, c_int},
+    slice,
+};
 use system::luaopen_system;
 
 mod renderer;
 mod renderer_font;
 mod system;
+mod system_large_file;
+mod system_window;
 
 static mut LIBS: [luaL_Reg; 2] = [
     luaL_Reg {
@@ -19,6 +26,59 @@ static mut LIBS: [luaL_Reg; 2] = [
     },
 ];
 
+/// Returns the bytes of the string argument at `idx`, which may contain NUL
+/// bytes.
+unsafe fn checkbytes<'a>(state: *mut lua_State, idx: c_int) -> &'a [u8] {
+    let mut len = 0;
+    let data = luaL_checklstring(state, idx, &mut len);
+    slice::from_raw_parts(data as *const u8, len)
+}
+
+/// Returns the string argument at `idx`, invalid UTF-8 sequences are replaced
+/// by the replacement character.
+unsafe fn checkstring<'a>(state: *mut lua_State, idx: c_int) -> Cow<'a, str> {
+    String::from_utf8_lossy(checkbytes(state, idx))
+}
+
+/// Returns the string argument at `idx`, or `default` when it is absent.
+unsafe fn optstring<'a>(state: *mut lua_State, idx: c_int, default: &'a str) -> Cow<'a, str> {
+    if lua_isnoneornil(state, idx) != 0 {
+        Cow::Borrowed(default)
+    } else {
+        checkstring(state, idx)
+    }
+}
+
+/// Cuts a string at its first NUL byte, where it would end anyway once passed
+/// to C.
+fn until_nul(text: &str) -> &str {
+    text.split('\0').next().unwrap_or(text)
+}
+
+unsafe fn checkpath(state: *mut lua_State, idx: c_int) -> OsString {
+    os_string_from_bytes(checkbytes(state, idx))
+}
+
+unsafe fn push_bytes(state: *mut lua_State, data: &[u8]) {
+    lua_pushlstring(state, data.as_ptr() as *const c_char, data.len());
+}
+
+unsafe fn push_string(state: *mut lua_State, text: &str) {
+    push_bytes(state, text.as_bytes());
+}
+
+#[cfg(unix)]
+pub(super) unsafe fn push_os_str(state: *mut lua_State, s: &OsStr) {
+    use std::os::unix::ffi::OsStrExt;
+
+    push_bytes(state, s.as_bytes());
+}
+
+#[cfg(windows)]
+pub(super) unsafe fn push_os_str(state: *mut lua_State, s: &OsStr) {
+    push_string(state, &s.to_string_lossy());
+}
+
 pub(super) unsafe extern "C" fn api_load_libs(state: *mut lua_State) {
     for lib in &LIBS {
         luaL_requiref(state, lib.name, lib.func, 1 as c_int);
@@ -1,17 +1,34 @@
 use crate::{
-    api::renderer_font::luaopen_renderer_font,
+    api::{checkstring, renderer_font::luaopen_renderer_font, system_window::WindowTarget},
     c_str,
     rencache::RenCache,
-    renderer::{RenColor, RenFont, RenRect},
+    renderer::{RenColor, RenFont, RenLine, RenRect},
     WINDOW,
 };
 use lua_sys::*;
 use once_cell::sync::Lazy;
-use std::{ffi::CStr, mem, os::raw::c_int, ptr, sync::Mutex};
+use std::{
+    mem,
+    os::raw::{c_char, c_int},
+    ptr,
+    sync::Mutex,
+};
 
 pub(super) static mut RENCACHE: Lazy<Mutex<RenCache>> =
     Lazy::new(|| Mutex::new(RenCache::init(unsafe { &WINDOW.lock().unwrap() })));
 
+/// The window selected by `begin_frame`, or null when drawing into the main
+/// window.
+pub(super) static mut RENDER_TARGET: *mut WindowTarget = ptr::null_mut();
+
+/// Runs `f` with the render cache of the window being drawn into.
+unsafe fn with_rencache<T>(f: impl FnOnce(&mut RenCache) -> T) -> T {
+    match RENDER_TARGET.as_mut() {
+        Some(target) => f(&mut target.rencache),
+        None => f(&mut RENCACHE.lock().unwrap()),
+    }
+}
+
 unsafe extern "C" fn checkcolor(state: *mut lua_State, idx: c_int, def: c_int) -> RenColor {
     let mut color = RenColor::default();
     if lua_type(state, idx) <= 0 {
@@ -36,26 +53,40 @@ unsafe extern "C" fn checkcolor(state: *mut lua_State, idx: c_int, def: c_int) -
 
 unsafe extern "C" fn f_show_debug(state: *mut lua_State) -> c_int {
     luaL_checkany(state, 1);
-    RENCACHE
-        .lock()
-        .unwrap()
-        .show_debug(lua_toboolean(state, 1) != 0);
+    let enable = lua_toboolean(state, 1) != 0;
+    with_rencache(|rencache| rencache.show_debug(enable));
     0
 }
 
-unsafe extern "C" fn f_begin_frame(_: *mut lua_State) -> c_int {
-    RENCACHE
-        .lock()
-        .unwrap()
-        .begin_frame(&WINDOW.lock().unwrap());
+unsafe extern "C" fn f_begin_frame(state: *mut lua_State) -> c_int {
+    RENDER_TARGET = if lua_type(state, 1) > 0 {
+        let target = luaL_checkudata(state, 1, c_str!("Window")) as *mut *mut WindowTarget;
+        if (*target).is_null() {
+            luaL_error(state, c_str!("window is closed"));
+        }
+        *target
+    } else {
+        ptr::null_mut()
+    };
+    match RENDER_TARGET.as_mut() {
+        Some(target) => target.rencache.begin_frame(&target.window),
+        None => RENCACHE
+            .lock()
+            .unwrap()
+            .begin_frame(&WINDOW.lock().unwrap()),
+    }
     0
 }
 
 unsafe extern "C" fn f_end_frame(_: *mut lua_State) -> c_int {
-    RENCACHE
-        .lock()
-        .unwrap()
-        .end_frame(&mut WINDOW.lock().unwrap());
+    match RENDER_TARGET.as_mut() {
+        Some(target) => target.rencache.end_frame(&mut target.window),
+        None => RENCACHE
+            .lock()
+            .unwrap()
+            .end_frame(&mut WINDOW.lock().unwrap()),
+    }
+    RENDER_TARGET = ptr::null_mut();
     0
 }
 
@@ -65,7 +96,7 @@ unsafe extern "C" fn f_set_clip_rect(state: *mut lua_State) -> c_int {
     rect.y = luaL_checknumber(state, 2) as c_int;
     rect.width = luaL_checknumber(state, 3) as c_int;
     rect.height = luaL_checknumber(state, 4) as c_int;
-    RENCACHE.lock().unwrap().set_clip_rect(rect);
+    with_rencache(|rencache| rencache.set_clip_rect(rect));
     0
 }
 
@@ -76,28 +107,94 @@ unsafe extern "C" fn f_draw_rect(state: *mut lua_State) -> c_int {
     rect.width = luaL_checknumber(state, 3) as c_int;
     rect.height = luaL_checknumber(state, 4) as c_int;
     let color = checkcolor(state, 5, 255);
-    RENCACHE.lock().unwrap().draw_rect(rect, color);
+    with_rencache(|rencache| rencache.draw_rect(rect, color));
+    0
+}
+
+unsafe extern "C" fn f_draw_line(state: *mut lua_State) -> c_int {
+    let mut line = RenLine::default();
+    line.x1 = luaL_checknumber(state, 1) as c_int;
+    line.y1 = luaL_checknumber(state, 2) as c_int;
+    line.x2 = luaL_checknumber(state, 3) as c_int;
+    line.y2 = luaL_checknumber(state, 4) as c_int;
+    let width = luaL_checknumber(state, 5) as c_int;
+    let color = checkcolor(state, 6, 255);
+    with_rencache(|rencache| rencache.draw_line(line, width, color));
+    0
+}
+
+unsafe extern "C" fn f_draw_rounded_rect(state: *mut lua_State) -> c_int {
+    let mut rect = RenRect::default();
+    rect.x = luaL_checknumber(state, 1) as c_int;
+    rect.y = luaL_checknumber(state, 2) as c_int;
+    rect.width = luaL_checknumber(state, 3) as c_int;
+    rect.height = luaL_checknumber(state, 4) as c_int;
+    let radius = luaL_checknumber(state, 5) as c_int;
+    let color = checkcolor(state, 6, 255);
+    let stroke = luaL_optnumber(state, 7, 0.0) as c_int;
+    with_rencache(|rencache| rencache.draw_rounded_rect(rect, radius, stroke, color));
+    0
+}
+
+unsafe extern "C" fn f_draw_circle(state: *mut lua_State) -> c_int {
+    let x = luaL_checknumber(state, 1) as c_int;
+    let y = luaL_checknumber(state, 2) as c_int;
+    let radius = luaL_checknumber(state, 3) as c_int;
+    let color = checkcolor(state, 4, 255);
+    let stroke = luaL_optnumber(state, 5, 0.0) as c_int;
+    let rect = RenRect {
+        x: x - radius,
+        y: y - radius,
+        width: radius * 2,
+        height: radius * 2,
+    };
+    with_rencache(|rencache| rencache.draw_rounded_rect(rect, radius, stroke, color));
+    0
+}
+
+static mut GRADIENT_OPTS: [*const c_char; 3] =
+    [c_str!("vertical"), c_str!("horizontal"), ptr::null()];
+
+unsafe extern "C" fn f_draw_gradient_rect(state: *mut lua_State) -> c_int {
+    let mut rect = RenRect::default();
+    rect.x = luaL_checknumber(state, 1) as c_int;
+    rect.y = luaL_checknumber(state, 2) as c_int;
+    rect.width = luaL_checknumber(state, 3) as c_int;
+    rect.height = luaL_checknumber(state, 4) as c_int;
+    let from = checkcolor(state, 5, 255);
+    let to = checkcolor(state, 6, 255);
+    let direction = luaL_checkoption(state, 7, c_str!("vertical"), GRADIENT_OPTS.as_ptr());
+    with_rencache(|rencache| rencache.draw_gradient_rect(rect, from, to, direction == 0));
+    0
+}
+
+unsafe extern "C" fn f_draw_shadow(state: *mut lua_State) -> c_int {
+    let mut rect = RenRect::default();
+    rect.x = luaL_checknumber(state, 1) as c_int;
+    rect.y = luaL_checknumber(state, 2) as c_int;
+    rect.width = luaL_checknumber(state, 3) as c_int;
+    rect.height = luaL_checknumber(state, 4) as c_int;
+    let blur = luaL_checknumber(state, 5) as c_int;
+    let color = checkcolor(state, 6, 0);
+    let radius = luaL_optnumber(state, 7, 0.0) as c_int;
+    with_rencache(|rencache| rencache.draw_shadow(rect, radius, blur.max(0), color));
     0
 }
 
 unsafe extern "C" fn f_draw_text(state: *mut lua_State) -> c_int {
     let font = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
-    let text = luaL_checklstring(state, 2, ptr::null_mut());
-    let text = CStr::from_ptr(text).to_str().unwrap();
+    let text = checkstring(state, 2);
     let mut x = luaL_checknumber(state, 3) as c_int;
     let y = luaL_checknumber(state, 4) as c_int;
     let color = checkcolor(state, 5, 255);
     if !(*font).is_null() {
-        x = RENCACHE
-            .lock()
-            .unwrap()
-            .draw_text(&mut **font, text, x, y, color);
+        x = with_rencache(|rencache| rencache.draw_text(&mut **font, &text, x, y, color));
     }
     lua_pushnumber(state, x as lua_Number);
     1
 }
 
-static mut LIB: [luaL_Reg; 7] = [
+static mut LIB: [luaL_Reg; 12] = [
     luaL_Reg {
         name: c_str!("show_debug"),
         func: Some(f_show_debug),
@@ -118,6 +215,26 @@ static mut LIB: [luaL_Reg; 7] = [
         name: c_str!("draw_rect"),
         func: Some(f_draw_rect),
     },
+    luaL_Reg {
+        name: c_str!("draw_line"),
+        func: Some(f_draw_line),
+    },
+    luaL_Reg {
+        name: c_str!("draw_rounded_rect"),
+        func: Some(f_draw_rounded_rect),
+    },
+    luaL_Reg {
+        name: c_str!("draw_circle"),
+        func: Some(f_draw_circle),
+    },
+    luaL_Reg {
+        name: c_str!("draw_gradient_rect"),
+        func: Some(f_draw_gradient_rect),
+    },
+    luaL_Reg {
+        name: c_str!("draw_shadow"),
+        func: Some(f_draw_shadow),
+    },
     luaL_Reg {
         name: c_str!("draw_text"),
         func: Some(f_draw_text),
@@ -132,7 +249,7 @@ pub(super) unsafe extern "C" fn luaopen_renderer(state: *mut lua_State) -> c_int
     lua_createtable(
         state,
         0,
-        mem::size_of::<[luaL_Reg; 7]>()
+        mem::size_of::<[luaL_Reg; 12]>()
             .wrapping_div(mem::size_of::<luaL_Reg>())
             .wrapping_sub(1) as c_int,
     );
@@ -1,15 +1,18 @@
-use crate::{api::renderer::RENCACHE, c_str, os_string_from_ptr, renderer::RenFont};
+use crate::{
+    api::{checkbytes, checkpath, checkstring, renderer::RENCACHE},
+    c_str,
+    renderer::RenFont,
+};
 use lua_sys::*;
 use std::{
-    ffi::CStr,
     mem,
+    ops::Range,
     os::raw::{c_float, c_int},
     ptr,
 };
 
 unsafe extern "C" fn f_load(state: *mut lua_State) -> c_int {
-    let filename = luaL_checklstring(state, 1, ptr::null_mut());
-    let filename = os_string_from_ptr(filename);
+    let filename = checkpath(state, 1);
     let size = luaL_checknumber(state, 2) as c_float;
     let self_0 = lua_newuserdata(state, mem::size_of::<*mut RenFont>()) as *mut *mut RenFont;
     luaL_setmetatable(state, c_str!("Font"));
@@ -23,6 +26,21 @@ unsafe extern "C" fn f_load(state: *mut lua_State) -> c_int {
     1
 }
 
+unsafe extern "C" fn f_load_memory(state: *mut lua_State) -> c_int {
+    let data = checkbytes(state, 1).to_vec();
+    let size = luaL_checknumber(state, 2) as c_float;
+    let self_0 = lua_newuserdata(state, mem::size_of::<*mut RenFont>()) as *mut *mut RenFont;
+    luaL_setmetatable(state, c_str!("Font"));
+    *self_0 = match RenFont::load_memory(data.into_boxed_slice(), size) {
+        Some(font) => Box::into_raw(font),
+        None => ptr::null_mut(),
+    };
+    if (*self_0).is_null() {
+        luaL_error(state, c_str!("failed to load font"));
+    }
+    1
+}
+
 unsafe extern "C" fn f_set_tab_width(state: *mut lua_State) -> c_int {
     let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
     let n = luaL_checknumber(state, 2) as c_int;
@@ -41,9 +59,8 @@ unsafe extern "C" fn f_gc(state: *mut lua_State) -> c_int {
 
 unsafe extern "C" fn f_get_width(state: *mut lua_State) -> c_int {
     let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
-    let text = luaL_checklstring(state, 2, ptr::null_mut());
-    let text = CStr::from_ptr(text).to_str().unwrap();
-    lua_pushnumber(state, (**self_0).measure_width(text) as lua_Number);
+    let text = checkstring(state, 2);
+    lua_pushnumber(state, (**self_0).measure_width(&text) as lua_Number);
     1
 }
 
@@ -53,7 +70,96 @@ unsafe extern "C" fn f_get_height(state: *mut lua_State) -> c_int {
     1
 }
 
-static mut LIB: [luaL_Reg; 6] = [
+unsafe extern "C" fn f_get_ascent(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    lua_pushnumber(state, (**self_0).ascent() as lua_Number);
+    1
+}
+
+unsafe extern "C" fn f_get_descent(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    lua_pushnumber(state, (**self_0).descent() as lua_Number);
+    1
+}
+
+unsafe extern "C" fn f_get_line_gap(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    lua_pushnumber(state, (**self_0).line_gap() as lua_Number);
+    1
+}
+
+unsafe extern "C" fn f_get_underline(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    let (position, thickness) = (**self_0).underline();
+    lua_pushnumber(state, position as lua_Number);
+    lua_pushnumber(state, thickness as lua_Number);
+    2
+}
+
+/// Reads a character given either as a string (its first character is used)
+/// or as a codepoint.
+unsafe fn checkchar(state: *mut lua_State, idx: c_int) -> char {
+    if lua_type(state, idx) == LUA_TNUMBER {
+        let codepoint = luaL_checkinteger(state, idx);
+        return char::from_u32(codepoint as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+    }
+    checkstring(state, idx).chars().next().unwrap_or('\0')
+}
+
+unsafe extern "C" fn f_get_advance(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    let codepoint = checkchar(state, 2);
+    lua_pushnumber(state, (**self_0).advance(codepoint) as lua_Number);
+    1
+}
+
+unsafe extern "C" fn f_has_glyph(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    let codepoint = checkchar(state, 2);
+    lua_pushboolean(state, (**self_0).has_glyph(codepoint) as c_int);
+    1
+}
+
+/// Converts `string.sub` style indices into a byte range of a string of
+/// `len` bytes.
+fn sub_range(len: usize, i: lua_Integer, j: lua_Integer) -> Range<usize> {
+    let len = len as lua_Integer;
+    let start = if i < 0 { len + i + 1 } else { i }.max(1);
+    let end = if j < 0 { len + j + 1 } else { j }.min(len);
+    if start > end {
+        0..0
+    } else {
+        (start - 1) as usize..end as usize
+    }
+}
+
+unsafe extern "C" fn f_get_width_of_range(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    let text = checkbytes(state, 2);
+    let i = luaL_optinteger(state, 3, 1);
+    let j = luaL_optinteger(state, 4, -1);
+    let text = String::from_utf8_lossy(&text[sub_range(text.len(), i, j)]);
+    lua_pushnumber(state, (**self_0).measure_width(&text) as lua_Number);
+    1
+}
+
+unsafe extern "C" fn f_char_index_at(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    let text = checkbytes(state, 2);
+    let x = luaL_checknumber(state, 3);
+    lua_pushinteger(state, (**self_0).char_index_at(text, x) as lua_Integer);
+    1
+}
+
+unsafe extern "C" fn f_x_offset(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Font")) as *mut *mut RenFont;
+    let text = checkbytes(state, 2);
+    let col = luaL_checkinteger(state, 3).max(0) as usize;
+    lua_pushnumber(state, (**self_0).x_offset(text, col) as lua_Number);
+    1
+}
+
+static mut LIB: [luaL_Reg; 16] = [
     luaL_Reg {
         name: c_str!("__gc"),
         func: Some(f_gc),
@@ -62,6 +168,10 @@ static mut LIB: [luaL_Reg; 6] = [
         name: c_str!("load"),
         func: Some(f_load),
     },
+    luaL_Reg {
+        name: c_str!("load_memory"),
+        func: Some(f_load_memory),
+    },
     luaL_Reg {
         name: c_str!("set_tab_width"),
         func: Some(f_set_tab_width),
@@ -74,6 +184,42 @@ static mut LIB: [luaL_Reg; 6] = [
         name: c_str!("get_height"),
         func: Some(f_get_height),
     },
+    luaL_Reg {
+        name: c_str!("get_ascent"),
+        func: Some(f_get_ascent),
+    },
+    luaL_Reg {
+        name: c_str!("get_descent"),
+        func: Some(f_get_descent),
+    },
+    luaL_Reg {
+        name: c_str!("get_line_gap"),
+        func: Some(f_get_line_gap),
+    },
+    luaL_Reg {
+        name: c_str!("get_underline"),
+        func: Some(f_get_underline),
+    },
+    luaL_Reg {
+        name: c_str!("get_advance"),
+        func: Some(f_get_advance),
+    },
+    luaL_Reg {
+        name: c_str!("has_glyph"),
+        func: Some(f_has_glyph),
+    },
+    luaL_Reg {
+        name: c_str!("get_width_of_range"),
+        func: Some(f_get_width_of_range),
+    },
+    luaL_Reg {
+        name: c_str!("char_index_at"),
+        func: Some(f_char_index_at),
+    },
+    luaL_Reg {
+        name: c_str!("x_offset"),
+        func: Some(f_x_offset),
+    },
     luaL_Reg {
         name: ptr::null(),
         func: None,
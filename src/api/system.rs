@@ -1,92 +1,144 @@
 use crate::{
-    api::renderer::RENCACHE,
-    c_str, os_string_from_ptr,
-    window::{Event, WindowMode},
+    analyze::analyze,
+    api::{
+        checkbytes, checkpath, checkstring, optstring, push_bytes, push_os_str, push_string,
+        renderer::RENCACHE, system_large_file::luaopen_system_large_file,
+        system_window::luaopen_system_window, until_nul,
+    },
+    atomic::{write_file_atomic, Backup, Written},
+    c_str,
+    clipboard::{self, Selection},
+    copy::copy_path,
+    encoding::{self, Encoding},
+    fonts::list_fonts,
+    trash,
+    window::{Event, FlashMode, HitRegion, HitTest, WindowGeometry, WindowMode},
     WINDOW,
 };
 use libc::system;
 use lua_sys::*;
 use sdl2::{
     messagebox::{show_message_box, ButtonData, MessageBoxButtonFlag, MessageBoxFlag},
-    mouse::{Cursor, SystemCursor},
+    rect::Rect,
     sys::SDL_WaitEventTimeout,
 };
 use std::{
     env::set_current_dir,
-    ffi::{CStr, CString},
-    fs, mem,
+    fs, io, mem,
     os::raw::{c_char, c_int},
+    path::{Path, PathBuf},
     ptr, thread,
     time::{Duration, SystemTime},
 };
 
-unsafe extern "C" fn f_poll_event(state: *mut lua_State) -> c_int {
-    match WINDOW.lock().unwrap().poll_event() {
-        Option::None => 0,
-        Some(Event::Quit) => {
+/// Pushes the name and the arguments of an event, returns the number of values
+/// pushed.
+pub(super) unsafe fn push_event(state: *mut lua_State, event: Event) -> c_int {
+    match event {
+        Event::Quit => {
             lua_pushstring(state, c_str!("quit"));
             1
         }
-        Some(Event::Resized { width, height }) => {
+        Event::Closed => {
+            lua_pushstring(state, c_str!("closed"));
+            1
+        }
+        Event::Resized { width, height } => {
             lua_pushstring(state, c_str!("resized"));
             lua_pushnumber(state, width as lua_Number);
             lua_pushnumber(state, height as lua_Number);
             3
         }
-        Some(Event::Exposed) => {
-            RENCACHE.lock().unwrap().invalidate();
+        Event::Exposed => {
             lua_pushstring(state, c_str!("exposed"));
             1
         }
-        Some(Event::FileDropped { file, x, y }) => {
-            let file = CString::new(file).unwrap();
+        Event::FocusGained => {
+            lua_pushstring(state, c_str!("focusgained"));
+            1
+        }
+        Event::FocusLost => {
+            lua_pushstring(state, c_str!("focuslost"));
+            1
+        }
+        Event::Minimized => {
+            lua_pushstring(state, c_str!("minimized"));
+            1
+        }
+        Event::Maximized => {
+            lua_pushstring(state, c_str!("maximized"));
+            1
+        }
+        Event::Restored => {
+            lua_pushstring(state, c_str!("restored"));
+            1
+        }
+        Event::MouseEntered => {
+            lua_pushstring(state, c_str!("mouseentered"));
+            1
+        }
+        Event::MouseLeft => {
+            lua_pushstring(state, c_str!("mouseleft"));
+            1
+        }
+        Event::FileDropped { file, x, y } => {
             lua_pushstring(state, c_str!("filedropped"));
-            lua_pushstring(state, file.as_ptr());
+            push_string(state, &file);
             lua_pushnumber(state, x as lua_Number);
             lua_pushnumber(state, y as lua_Number);
             4
         }
-        Some(Event::KeyPressed { key }) => {
-            let key = CString::new(key).unwrap();
+        Event::TextDropped { text, x, y } => {
+            lua_pushstring(state, c_str!("textdropped"));
+            push_string(state, &text);
+            lua_pushnumber(state, x as lua_Number);
+            lua_pushnumber(state, y as lua_Number);
+            4
+        }
+        Event::DropBegin => {
+            lua_pushstring(state, c_str!("dropbegin"));
+            1
+        }
+        Event::DropComplete => {
+            lua_pushstring(state, c_str!("dropcomplete"));
+            1
+        }
+        Event::KeyPressed { key } => {
             lua_pushstring(state, c_str!("keypressed"));
-            lua_pushstring(state, key.as_ptr());
+            push_string(state, &key);
             2
         }
-        Some(Event::KeyReleased { key }) => {
-            let key = CString::new(key).unwrap();
+        Event::KeyReleased { key } => {
             lua_pushstring(state, c_str!("keyreleased"));
-            lua_pushstring(state, key.as_ptr());
+            push_string(state, &key);
             2
         }
-        Some(Event::TextInput { text }) => {
-            let text = CString::new(text).unwrap();
+        Event::TextInput { text } => {
             lua_pushstring(state, c_str!("textinput"));
-            lua_pushstring(state, text.as_ptr());
+            push_string(state, &text);
             2
         }
-        Some(Event::MousePressed {
+        Event::MousePressed {
             button,
             x,
             y,
             clicks,
-        }) => {
-            let name = CString::new(button.name()).unwrap();
+        } => {
             lua_pushstring(state, c_str!("mousepressed"));
-            lua_pushstring(state, name.as_ptr());
+            push_string(state, button.name());
             lua_pushnumber(state, x as lua_Number);
             lua_pushnumber(state, y as lua_Number);
             lua_pushnumber(state, clicks as lua_Number);
             5
         }
-        Some(Event::MouseReleased { button, x, y }) => {
-            let name = CString::new(button.name()).unwrap();
+        Event::MouseReleased { button, x, y } => {
             lua_pushstring(state, c_str!("mousereleased"));
-            lua_pushstring(state, name.as_ptr());
+            push_string(state, button.name());
             lua_pushnumber(state, x as lua_Number);
             lua_pushnumber(state, y as lua_Number);
             4
         }
-        Some(Event::MouseMoved { x, y, xrel, yrel }) => {
+        Event::MouseMoved { x, y, xrel, yrel } => {
             lua_pushstring(state, c_str!("mousemoved"));
             lua_pushnumber(state, x as lua_Number);
             lua_pushnumber(state, y as lua_Number);
@@ -94,10 +146,70 @@ unsafe extern "C" fn f_poll_event(state: *mut lua_State) -> c_int {
             lua_pushnumber(state, yrel as lua_Number);
             5
         }
-        Some(Event::MouseWheel { y }) => {
+        Event::MouseWheel { x, y } => {
             lua_pushstring(state, c_str!("mousewheel"));
             lua_pushnumber(state, y as lua_Number);
-            2
+            lua_pushnumber(state, x as lua_Number);
+            3
+        }
+        Event::TouchPressed { finger, x, y } => {
+            lua_pushstring(state, c_str!("touchpressed"));
+            lua_pushnumber(state, x as lua_Number);
+            lua_pushnumber(state, y as lua_Number);
+            lua_pushnumber(state, finger as lua_Number);
+            4
+        }
+        Event::TouchReleased { finger, x, y } => {
+            lua_pushstring(state, c_str!("touchreleased"));
+            lua_pushnumber(state, x as lua_Number);
+            lua_pushnumber(state, y as lua_Number);
+            lua_pushnumber(state, finger as lua_Number);
+            4
+        }
+        Event::TouchMoved {
+            finger,
+            x,
+            y,
+            dx,
+            dy,
+        } => {
+            lua_pushstring(state, c_str!("touchmoved"));
+            lua_pushnumber(state, x as lua_Number);
+            lua_pushnumber(state, y as lua_Number);
+            lua_pushnumber(state, dx as lua_Number);
+            lua_pushnumber(state, dy as lua_Number);
+            lua_pushnumber(state, finger as lua_Number);
+            6
+        }
+        Event::Gesture {
+            pinch,
+            rotation,
+            x,
+            y,
+            fingers,
+        } => {
+            lua_pushstring(state, c_str!("gesture"));
+            lua_pushnumber(state, pinch as lua_Number);
+            lua_pushnumber(state, rotation as lua_Number);
+            lua_pushnumber(state, x as lua_Number);
+            lua_pushnumber(state, y as lua_Number);
+            lua_pushnumber(state, fingers as lua_Number);
+            6
+        }
+    }
+}
+
+/// Polls the events of the main window, the events of the windows opened from
+/// Lua are kept until they are polled through their own `poll_event`.
+unsafe extern "C" fn f_poll_event(state: *mut lua_State) -> c_int {
+    let event = WINDOW.lock().unwrap().poll_event();
+    match event {
+        Option::None => 0,
+        Some(event) => {
+            if let Event::Exposed = event {
+                RENCACHE.lock().unwrap().invalidate();
+            }
+            push_event(state, event)
         }
     }
 }
@@ -113,46 +225,49 @@ unsafe extern "C" fn f_wait_event(state: *mut lua_State) -> c_int {
     1
 }
 
-static mut CURSOR_OPTS: [*const c_char; 6] = [
-    c_str!("arrow"),
-    c_str!("ibeam"),
-    c_str!("sizeh"),
-    c_str!("sizev"),
-    c_str!("hand"),
-    ptr::null(),
-];
-
-static mut CURSOR_ENUMS: [SystemCursor; 5] = [
-    SystemCursor::Arrow,
-    SystemCursor::IBeam,
-    SystemCursor::SizeWE,
-    SystemCursor::SizeNS,
-    SystemCursor::Hand,
-];
-
 unsafe extern "C" fn f_set_cursor(state: *mut lua_State) -> c_int {
-    let opt = luaL_checkoption(
-        state,
-        1,
-        c_str!("arrow"),
-        CURSOR_OPTS.as_mut_ptr() as *const *const c_char,
-    );
-    let n = CURSOR_ENUMS[opt as usize];
-    Cursor::from_system(n).expect("Could not set cursor").set();
+    let name = optstring(state, 1, "arrow");
+    let found = WINDOW.lock().unwrap().set_cursor(&name);
+    if !found {
+        luaL_error(
+            state,
+            c_str!("unknown cursor '%s'"),
+            lua_tolstring(state, 1, ptr::null_mut()),
+        );
+    }
     0
 }
 
+unsafe extern "C" fn f_create_cursor(state: *mut lua_State) -> c_int {
+    let name = checkstring(state, 1);
+    let path = checkpath(state, 2);
+    let hot_x = luaL_optnumber(state, 3, 0.0) as i32;
+    let hot_y = luaL_optnumber(state, 4, 0.0) as i32;
+    let result = WINDOW
+        .lock()
+        .unwrap()
+        .create_cursor(&name, Path::new(&path), hot_x, hot_y);
+    if let Err(error) = result {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
 unsafe extern "C" fn f_set_window_title(state: *mut lua_State) -> c_int {
-    let title = luaL_checklstring(state, 1, ptr::null_mut());
-    let title = CStr::from_ptr(title).to_str().unwrap();
-    WINDOW.lock().unwrap().set_title(title);
+    let title = checkstring(state, 1);
+    WINDOW.lock().unwrap().set_title(until_nul(&title));
     0
 }
 
-static mut WINDOW_OPTS: [*const c_char; 4] = [
+pub(super) static mut WINDOW_OPTS: [*const c_char; 6] = [
     c_str!("normal"),
     c_str!("maximized"),
     c_str!("fullscreen"),
+    c_str!("minimized"),
+    c_str!("borderless"),
     ptr::null(),
 ];
 unsafe extern "C" fn f_set_window_mode(state: *mut lua_State) -> c_int {
@@ -161,6 +276,193 @@ unsafe extern "C" fn f_set_window_mode(state: *mut lua_State) -> c_int {
     0
 }
 
+unsafe extern "C" fn f_get_window_mode(state: *mut lua_State) -> c_int {
+    push_string(state, WINDOW.lock().unwrap().mode().name());
+    1
+}
+
+pub(super) unsafe fn push_geometry(state: *mut lua_State, geometry: WindowGeometry) -> c_int {
+    lua_pushnumber(state, geometry.x as lua_Number);
+    lua_pushnumber(state, geometry.y as lua_Number);
+    lua_pushnumber(state, geometry.width as lua_Number);
+    lua_pushnumber(state, geometry.height as lua_Number);
+    lua_pushnumber(state, geometry.display as lua_Number);
+    5
+}
+
+pub(super) unsafe fn checkgeometry(state: *mut lua_State, idx: c_int) -> WindowGeometry {
+    WindowGeometry {
+        x: luaL_checknumber(state, idx) as i32,
+        y: luaL_checknumber(state, idx + 1) as i32,
+        width: luaL_checknumber(state, idx + 2) as u32,
+        height: luaL_checknumber(state, idx + 3) as u32,
+        display: luaL_optnumber(state, idx + 4, 0.0) as i32,
+    }
+}
+
+unsafe extern "C" fn f_get_window_geometry(state: *mut lua_State) -> c_int {
+    let geometry = WINDOW.lock().unwrap().geometry();
+    push_geometry(state, geometry)
+}
+
+unsafe extern "C" fn f_set_window_geometry(state: *mut lua_State) -> c_int {
+    let geometry = checkgeometry(state, 1);
+    let result = WINDOW.lock().unwrap().set_geometry(geometry);
+    if let Err(error) = result {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+static mut HIT_TEST_OPTS: [*const c_char; 11] = [
+    c_str!("normal"),
+    c_str!("draggable"),
+    c_str!("topleft"),
+    c_str!("top"),
+    c_str!("topright"),
+    c_str!("right"),
+    c_str!("bottomright"),
+    c_str!("bottom"),
+    c_str!("bottomleft"),
+    c_str!("left"),
+    ptr::null(),
+];
+
+/// Reads a list of `{ x, y, w, h, hit }` regions.
+pub(super) unsafe fn checkhitregions(state: *mut lua_State, idx: c_int) -> Vec<HitRegion> {
+    let mut regions = Vec::new();
+    if lua_isnoneornil(state, idx) != 0 {
+        return regions;
+    }
+    luaL_checktype(state, idx, LUA_TTABLE);
+    let count = lua_rawlen(state, idx);
+    for i in 1..=count {
+        lua_rawgeti(state, idx, i as lua_Integer);
+        let region = lua_gettop(state);
+        luaL_checktype(state, region, LUA_TTABLE);
+        for field in 1..=5 {
+            lua_rawgeti(state, region, field);
+        }
+        let hit = luaL_checkoption(state, -1, c_str!("normal"), HIT_TEST_OPTS.as_ptr());
+        regions.push(HitRegion {
+            x: luaL_checknumber(state, -5) as i32,
+            y: luaL_checknumber(state, -4) as i32,
+            width: luaL_checknumber(state, -3) as i32,
+            height: luaL_checknumber(state, -2) as i32,
+            hit: HitTest::from_raw(hit),
+        });
+        lua_settop(state, region - 1);
+    }
+    regions
+}
+
+unsafe extern "C" fn f_set_window_hit_regions(state: *mut lua_State) -> c_int {
+    let regions = checkhitregions(state, 1);
+    let result = WINDOW.lock().unwrap().set_hit_regions(regions);
+    if let Err(error) = result {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_set_window_opacity(state: *mut lua_State) -> c_int {
+    let opacity = luaL_checknumber(state, 1) as f32;
+    let result = WINDOW.lock().unwrap().set_opacity(opacity);
+    if let Err(error) = result {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_set_window_always_on_top(state: *mut lua_State) -> c_int {
+    let on_top = lua_toboolean(state, 1) != 0;
+    let result = WINDOW.lock().unwrap().set_always_on_top(on_top);
+    if let Err(error) = result {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+pub(super) static mut FLASH_OPTS: [*const c_char; 4] = [
+    c_str!("cancel"),
+    c_str!("briefly"),
+    c_str!("untilfocused"),
+    ptr::null(),
+];
+
+unsafe extern "C" fn f_flash_window(state: *mut lua_State) -> c_int {
+    let n = luaL_checkoption(state, 1, c_str!("briefly"), FLASH_OPTS.as_ptr());
+    let result = WINDOW.lock().unwrap().flash(FlashMode::from_raw(n));
+    if let Err(error) = result {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe fn push_rect(state: *mut lua_State, rect: Rect) {
+    lua_createtable(state, 0, 4);
+    lua_pushnumber(state, rect.x() as lua_Number);
+    lua_setfield(state, -2, c_str!("x"));
+    lua_pushnumber(state, rect.y() as lua_Number);
+    lua_setfield(state, -2, c_str!("y"));
+    lua_pushnumber(state, rect.width() as lua_Number);
+    lua_setfield(state, -2, c_str!("w"));
+    lua_pushnumber(state, rect.height() as lua_Number);
+    lua_setfield(state, -2, c_str!("h"));
+}
+
+unsafe extern "C" fn f_get_displays(state: *mut lua_State) -> c_int {
+    let result = WINDOW.lock().unwrap().displays();
+    let (displays, current) = match result {
+        Ok(displays) => displays,
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error);
+            return 2;
+        }
+    };
+    lua_createtable(state, displays.len() as c_int, 0);
+    for (i, display) in displays.into_iter().enumerate() {
+        lua_createtable(state, 0, 8);
+        lua_pushnumber(state, display.index as lua_Number);
+        lua_setfield(state, -2, c_str!("index"));
+        push_string(state, &display.name);
+        lua_setfield(state, -2, c_str!("name"));
+        push_rect(state, display.bounds);
+        lua_setfield(state, -2, c_str!("bounds"));
+        push_rect(state, display.usable_bounds);
+        lua_setfield(state, -2, c_str!("usable_bounds"));
+        if let Some((ddpi, hdpi, vdpi)) = display.dpi {
+            lua_pushnumber(state, ddpi as lua_Number);
+            lua_setfield(state, -2, c_str!("dpi"));
+            lua_pushnumber(state, hdpi as lua_Number);
+            lua_setfield(state, -2, c_str!("hdpi"));
+            lua_pushnumber(state, vdpi as lua_Number);
+            lua_setfield(state, -2, c_str!("vdpi"));
+        }
+        lua_pushnumber(state, display.refresh_rate as lua_Number);
+        lua_setfield(state, -2, c_str!("refresh_rate"));
+        lua_rawseti(state, -2, i as i64 + 1);
+    }
+    lua_pushnumber(state, current as lua_Number);
+    2
+}
+
 unsafe extern "C" fn f_window_has_focus(state: *mut lua_State) -> c_int {
     lua_pushboolean(state, WINDOW.lock().unwrap().has_focus() as c_int);
     1
@@ -174,10 +476,8 @@ unsafe extern "C" fn f_get_size(state: *mut lua_State) -> c_int {
 }
 
 unsafe extern "C" fn f_show_confirm_dialog(state: *mut lua_State) -> c_int {
-    let title = luaL_checklstring(state, 1, ptr::null_mut());
-    let title = CStr::from_ptr(title).to_str().unwrap();
-    let message = luaL_checklstring(state, 2, ptr::null_mut());
-    let message = CStr::from_ptr(message).to_str().unwrap();
+    let title = checkstring(state, 1);
+    let message = checkstring(state, 2);
     let buttons = [
         ButtonData {
             flags: MessageBoxButtonFlag::RETURNKEY_DEFAULT,
@@ -193,8 +493,8 @@ unsafe extern "C" fn f_show_confirm_dialog(state: *mut lua_State) -> c_int {
     let button = show_message_box(
         MessageBoxFlag::empty(),
         &buttons,
-        title,
-        message,
+        until_nul(&title),
+        until_nul(&message),
         Option::None,
         Option::None,
     )
@@ -210,8 +510,7 @@ unsafe extern "C" fn f_show_confirm_dialog(state: *mut lua_State) -> c_int {
 }
 
 unsafe extern "C" fn f_chdir(state: *mut lua_State) -> c_int {
-    let path = luaL_checklstring(state, 1, ptr::null_mut());
-    let path = os_string_from_ptr(path);
+    let path = checkpath(state, 1);
     if set_current_dir(path).is_err() {
         luaL_error(state, c_str!("chdir() failed"));
     }
@@ -219,100 +518,487 @@ unsafe extern "C" fn f_chdir(state: *mut lua_State) -> c_int {
 }
 
 unsafe extern "C" fn f_list_dir(state: *mut lua_State) -> c_int {
-    let path = luaL_checklstring(state, 1, ptr::null_mut());
-    let path = os_string_from_ptr(path);
+    let path = checkpath(state, 1);
+    let details = if lua_isnoneornil(state, 2) != 0 {
+        false
+    } else {
+        luaL_checktype(state, 2, LUA_TTABLE);
+        lua_getfield(state, 2, c_str!("details"));
+        let details = lua_toboolean(state, -1) != 0;
+        lua_settop(state, -2);
+        details
+    };
     let dir = match fs::read_dir(path) {
         Ok(dir) => dir,
         Err(error) => {
-            let message = CString::new(error.to_string()).unwrap();
             lua_pushnil(state);
-            lua_pushstring(state, message.as_ptr());
+            push_string(state, &error.to_string());
             return 2;
         }
     };
     lua_createtable(state, 0, 0);
-    for (i, entry) in dir.enumerate() {
-        match entry {
-            Err(_) => break,
-            Ok(entry) => {
-                let name = CString::new(entry.file_name().to_string_lossy().to_string()).unwrap();
-                lua_pushstring(state, name.as_ptr());
-                lua_rawseti(state, -2, i as i64);
+    // Entries which cannot be read are skipped.
+    for (i, entry) in dir.flatten().enumerate() {
+        if !details {
+            push_os_str(state, &entry.file_name());
+            lua_rawseti(state, -2, i as i64 + 1);
+            continue;
+        }
+        lua_createtable(state, 0, 4);
+        push_os_str(state, &entry.file_name());
+        lua_setfield(state, -2, c_str!("name"));
+        // Symlinks are followed like `get_file_info` does, unless they are
+        // broken.
+        let metadata = match entry.file_type() {
+            Ok(file_type) if file_type.is_symlink() => {
+                fs::metadata(entry.path()).or_else(|_| entry.metadata())
+            }
+            _ => entry.metadata(),
+        };
+        if let Ok(metadata) = metadata {
+            push_string(state, file_type_name(metadata.file_type()));
+            lua_setfield(state, -2, c_str!("type"));
+            lua_pushnumber(state, metadata.len() as lua_Number);
+            lua_setfield(state, -2, c_str!("size"));
+            if let Some(modified) = unix_time(metadata.modified()) {
+                lua_pushnumber(state, modified as lua_Number);
+                lua_setfield(state, -2, c_str!("modified"));
             }
         }
+        lua_rawseti(state, -2, i as i64 + 1);
     }
     1
 }
 
+/// Pushes `true`, or `nil` and the error message if the operation failed.
+unsafe fn push_io_result(state: *mut lua_State, result: io::Result<()>) -> c_int {
+    match result {
+        Ok(()) => {
+            lua_pushboolean(state, 1);
+            1
+        }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error.to_string());
+            2
+        }
+    }
+}
+
+unsafe extern "C" fn f_mkdir(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    let result = if lua_toboolean(state, 2) != 0 {
+        fs::create_dir_all(path)
+    } else {
+        fs::create_dir(path)
+    };
+    push_io_result(state, result)
+}
+
+unsafe extern "C" fn f_rmdir(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    let result = if lua_toboolean(state, 2) != 0 {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_dir(path)
+    };
+    push_io_result(state, result)
+}
+
+unsafe extern "C" fn f_remove(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    push_io_result(state, fs::remove_file(path))
+}
+
+unsafe extern "C" fn f_rename(state: *mut lua_State) -> c_int {
+    let from = checkpath(state, 1);
+    let to = checkpath(state, 2);
+    push_io_result(state, fs::rename(from, to))
+}
+
+unsafe extern "C" fn f_copy(state: *mut lua_State) -> c_int {
+    let from = checkpath(state, 1);
+    let to = checkpath(state, 2);
+    push_io_result(state, copy_path(Path::new(&from), Path::new(&to)))
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(windows)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    // Only the owner write bit has a meaning on Windows.
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+unsafe extern "C" fn f_chmod(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    let mode = luaL_checkinteger(state, 2) as u32;
+    push_io_result(state, set_mode(Path::new(&path), mode))
+}
+
+unsafe extern "C" fn f_write_file_atomic(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    let data = checkbytes(state, 2);
+    // The backup option is either a path or `true` to append "~" to the path.
+    let backup = if lua_isnoneornil(state, 3) != 0 {
+        None
+    } else {
+        luaL_checktype(state, 3, LUA_TTABLE);
+        lua_getfield(state, 3, c_str!("backup"));
+        let backup = if lua_type(state, -1) == LUA_TSTRING {
+            Some(Backup::Path(PathBuf::from(checkpath(state, -1))))
+        } else if lua_toboolean(state, -1) != 0 {
+            Some(Backup::Tilde)
+        } else {
+            None
+        };
+        lua_settop(state, -2);
+        backup
+    };
+    // A second value tells whether the file was overwritten in place.
+    match write_file_atomic(Path::new(&path), data, backup) {
+        Ok(written) => {
+            lua_pushboolean(state, 1);
+            lua_pushboolean(state, (written == Written::InPlace) as c_int);
+            2
+        }
+        Err(error) => push_io_result(state, Err(error)),
+    }
+}
+
+unsafe fn checkencoding(state: *mut lua_State, idx: c_int) -> Encoding {
+    match Encoding::from_name(&checkstring(state, idx)) {
+        Some(encoding) => encoding,
+        None => {
+            luaL_error(
+                state,
+                c_str!("unknown encoding '%s'"),
+                lua_tolstring(state, idx, ptr::null_mut()),
+            );
+            unreachable!()
+        }
+    }
+}
+
+unsafe extern "C" fn f_detect_encoding(state: *mut lua_State) -> c_int {
+    let (encoding, bom) = encoding::detect(checkbytes(state, 1));
+    push_string(state, encoding.name());
+    lua_pushboolean(state, bom as c_int);
+    2
+}
+
+unsafe extern "C" fn f_convert_encoding(state: *mut lua_State) -> c_int {
+    let data = checkbytes(state, 1);
+    let from = checkencoding(state, 2);
+    let to = checkencoding(state, 3);
+    let bom = if lua_isnoneornil(state, 4) != 0 {
+        false
+    } else {
+        luaL_checktype(state, 4, LUA_TTABLE);
+        lua_getfield(state, 4, c_str!("bom"));
+        let bom = lua_toboolean(state, -1) != 0;
+        lua_settop(state, -2);
+        bom
+    };
+    let result = encoding::decode(data, from).and_then(|text| encoding::encode(&text, to, bom));
+    match result {
+        Ok(data) => {
+            push_bytes(state, &data);
+            1
+        }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error);
+            2
+        }
+    }
+}
+
+unsafe extern "C" fn f_analyze_text(state: *mut lua_State) -> c_int {
+    let info = analyze(checkbytes(state, 1));
+    lua_createtable(state, 0, 6);
+    if let Some(line_ending) = info.line_ending {
+        push_string(state, line_ending.name());
+        lua_setfield(state, -2, c_str!("line_ending"));
+    }
+    lua_pushboolean(state, info.mixed_line_endings as c_int);
+    lua_setfield(state, -2, c_str!("mixed_line_endings"));
+    if let Some(indent_type) = info.indent_type {
+        push_string(state, indent_type.name());
+        lua_setfield(state, -2, c_str!("indent_type"));
+    }
+    if let Some(indent_size) = info.indent_size {
+        lua_pushinteger(state, indent_size as lua_Integer);
+        lua_setfield(state, -2, c_str!("indent_size"));
+    }
+    lua_pushboolean(state, info.trailing_newline as c_int);
+    lua_setfield(state, -2, c_str!("trailing_newline"));
+    lua_pushboolean(state, info.bom as c_int);
+    lua_setfield(state, -2, c_str!("bom"));
+    1
+}
+
+unsafe extern "C" fn f_trash(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    match trash::trash(Path::new(&path)) {
+        Ok(name) => {
+            push_os_str(state, &name);
+            1
+        }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error.to_string());
+            2
+        }
+    }
+}
+
+unsafe extern "C" fn f_list_trash(state: *mut lua_State) -> c_int {
+    let entries = match trash::list_trash() {
+        Ok(entries) => entries,
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error.to_string());
+            return 2;
+        }
+    };
+    lua_createtable(state, entries.len() as c_int, 0);
+    for (i, entry) in entries.iter().enumerate() {
+        lua_createtable(state, 0, 3);
+        push_os_str(state, &entry.name);
+        lua_setfield(state, -2, c_str!("name"));
+        push_os_str(state, entry.path.as_os_str());
+        lua_setfield(state, -2, c_str!("path"));
+        push_string(state, &entry.deletion_date);
+        lua_setfield(state, -2, c_str!("deletion_date"));
+        lua_rawseti(state, -2, i as i64 + 1);
+    }
+    1
+}
+
+unsafe extern "C" fn f_restore_from_trash(state: *mut lua_State) -> c_int {
+    let name = checkpath(state, 1);
+    match trash::restore(&name) {
+        Ok(path) => {
+            push_os_str(state, path.as_os_str());
+            1
+        }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error.to_string());
+            2
+        }
+    }
+}
+
 unsafe extern "C" fn f_absolute_path(state: *mut lua_State) -> c_int {
-    let path = luaL_checklstring(state, 1, ptr::null_mut());
-    let path = os_string_from_ptr(path);
+    let path = checkpath(state, 1);
     match fs::canonicalize(path) {
         Err(_) => 0,
         Ok(res) => {
-            let res = CString::new(res.to_string_lossy().to_string()).unwrap();
-            lua_pushstring(state, res.as_ptr());
+            push_os_str(state, res.as_os_str());
             1
         }
     }
 }
 
+/// Returns a time as a number of seconds since the epoch, if the filesystem
+/// supports it.
+fn unix_time(time: io::Result<SystemTime>) -> Option<f64> {
+    let time = time.ok()?;
+    Some(match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(error) => -error.duration().as_secs_f64(),
+    })
+}
+
+fn file_type_name(file_type: fs::FileType) -> &'static str {
+    #[cfg(unix)]
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_file() {
+        return "file";
+    } else if file_type.is_dir() {
+        return "dir";
+    } else if file_type.is_symlink() {
+        // Only reported for broken symlinks, others are followed.
+        return "symlink";
+    }
+    #[cfg(unix)]
+    {
+        if file_type.is_fifo() {
+            return "fifo";
+        } else if file_type.is_socket() {
+            return "socket";
+        } else if file_type.is_char_device() {
+            return "char";
+        } else if file_type.is_block_device() {
+            return "block";
+        }
+    }
+    "other"
+}
+
+#[cfg(unix)]
+unsafe fn push_unix_file_info(state: *mut lua_State, metadata: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    let mode = metadata.mode() & 0o7777;
+    lua_pushinteger(state, mode as lua_Integer);
+    lua_setfield(state, -2, c_str!("mode"));
+    lua_pushboolean(state, (mode & 0o111 != 0 && metadata.is_file()) as c_int);
+    lua_setfield(state, -2, c_str!("executable"));
+    lua_pushinteger(state, metadata.uid() as lua_Integer);
+    lua_setfield(state, -2, c_str!("uid"));
+    lua_pushinteger(state, metadata.gid() as lua_Integer);
+    lua_setfield(state, -2, c_str!("gid"));
+    lua_pushinteger(state, metadata.ino() as lua_Integer);
+    lua_setfield(state, -2, c_str!("inode"));
+    lua_pushinteger(state, metadata.dev() as lua_Integer);
+    lua_setfield(state, -2, c_str!("device"));
+}
+
+#[cfg(windows)]
+unsafe fn push_unix_file_info(_state: *mut lua_State, _metadata: &fs::Metadata) {}
+
 unsafe extern "C" fn f_get_file_info(state: *mut lua_State) -> c_int {
-    let path = luaL_checklstring(state, 1, ptr::null_mut());
-    let path = os_string_from_ptr(path);
-    match fs::metadata(path) {
+    let path = checkpath(state, 1);
+    // Symlinks are followed, unless they are broken.
+    let metadata = fs::symlink_metadata(&path).map(|link| {
+        let symlink = link.file_type().is_symlink();
+        if symlink {
+            let target = fs::read_link(&path).ok();
+            (fs::metadata(&path).unwrap_or(link), symlink, target)
+        } else {
+            (link, symlink, None)
+        }
+    });
+    match metadata {
         Err(error) => {
-            let message = CString::new(error.to_string()).unwrap();
             lua_pushnil(state);
-            lua_pushstring(state, message.as_ptr());
+            push_string(state, &error.to_string());
             2
         }
-        Ok(s) => {
+        Ok((s, symlink, target)) => {
             lua_createtable(state, 0, 0);
-            lua_pushnumber(
-                state,
-                s.modified()
-                    .unwrap()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .expect("Current date should not be set before 1970")
-                    .as_secs_f64() as lua_Number,
-            );
-            lua_setfield(state, -2, c_str!("modified"));
+            let times = [
+                (c_str!("modified"), s.modified()),
+                (c_str!("accessed"), s.accessed()),
+                (c_str!("created"), s.created()),
+            ];
+            for (name, time) in times {
+                if let Some(time) = unix_time(time) {
+                    lua_pushnumber(state, time as lua_Number);
+                    lua_setfield(state, -2, name);
+                }
+            }
             lua_pushnumber(state, s.len() as lua_Number);
             lua_setfield(state, -2, c_str!("size"));
-            if s.file_type().is_file() {
-                lua_pushstring(state, c_str!("file"));
-            } else if s.file_type().is_dir() {
-                lua_pushstring(state, c_str!("dir"));
-            } else {
-                lua_pushnil(state);
-            }
+            push_string(state, file_type_name(s.file_type()));
             lua_setfield(state, -2, c_str!("type"));
+            lua_pushboolean(state, symlink as c_int);
+            lua_setfield(state, -2, c_str!("symlink"));
+            if let Some(target) = target {
+                push_os_str(state, target.as_os_str());
+                lua_setfield(state, -2, c_str!("target"));
+            }
+            lua_pushboolean(state, s.permissions().readonly() as c_int);
+            lua_setfield(state, -2, c_str!("readonly"));
+            push_unix_file_info(state, &s);
             1
         }
     }
 }
 
+unsafe extern "C" fn f_list_fonts(state: *mut lua_State) -> c_int {
+    let fonts = list_fonts();
+    lua_createtable(state, fonts.len() as c_int, 0);
+    for (i, font) in fonts.iter().enumerate() {
+        lua_createtable(state, 0, 3);
+        push_os_str(state, font.path.as_os_str());
+        lua_setfield(state, -2, c_str!("path"));
+        push_string(state, &font.family);
+        lua_setfield(state, -2, c_str!("family"));
+        push_string(state, &font.style);
+        lua_setfield(state, -2, c_str!("style"));
+        lua_rawseti(state, -2, i as i64 + 1);
+    }
+    1
+}
+
+/// Reads the `primary` option of a clipboard function, which selects the
+/// primary selection instead of the clipboard.
+unsafe fn checkselection(state: *mut lua_State, idx: c_int) -> Selection {
+    if lua_isnoneornil(state, idx) != 0 {
+        return Selection::Clipboard;
+    }
+    luaL_checktype(state, idx, LUA_TTABLE);
+    lua_getfield(state, idx, c_str!("primary"));
+    let primary = lua_toboolean(state, -1) != 0;
+    lua_settop(state, -2);
+    if primary {
+        Selection::Primary
+    } else {
+        Selection::Clipboard
+    }
+}
+
 unsafe extern "C" fn f_get_clipboard(state: *mut lua_State) -> c_int {
-    let clipboard = WINDOW.lock().unwrap().clipboard();
-    let text = clipboard.clipboard_text().ok();
-    match text {
-        Option::None => 0,
-        Some(text) => {
-            let text = CString::new(text).unwrap();
-            lua_pushstring(state, text.as_ptr());
+    let selection = checkselection(state, 1);
+    match clipboard::get_text(selection) {
+        Ok(text) => {
+            push_string(state, &text);
             1
         }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error);
+            2
+        }
     }
 }
 
 unsafe extern "C" fn f_set_clipboard(state: *mut lua_State) -> c_int {
-    let text = luaL_checklstring(state, 1, ptr::null_mut());
-    let clipboard = WINDOW.lock().unwrap().clipboard();
-    clipboard
-        .set_clipboard_text(CStr::from_ptr(text).to_str().unwrap())
-        .expect("Could not set clipboard");
-    0
+    let text = checkstring(state, 1);
+    let selection = checkselection(state, 2);
+    // An HTML version of the text can be offered along with it.
+    let html = if lua_isnoneornil(state, 2) != 0 {
+        None
+    } else {
+        lua_getfield(state, 2, c_str!("html"));
+        let html = if lua_isnoneornil(state, -1) != 0 {
+            None
+        } else {
+            Some(checkstring(state, -1).into_owned())
+        };
+        lua_settop(state, -2);
+        html
+    };
+    match clipboard::set_text(selection, &text, html.as_deref()) {
+        Ok(html_offered) => {
+            lua_pushboolean(state, 1);
+            lua_pushboolean(state, html_offered as c_int);
+            2
+        }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error);
+            2
+        }
+    }
+}
+
+unsafe extern "C" fn f_has_clipboard_text(state: *mut lua_State) -> c_int {
+    let selection = checkselection(state, 1);
+    lua_pushboolean(state, clipboard::has_text(selection) as c_int);
+    1
 }
 
 unsafe extern "C" fn f_get_time(state: *mut lua_State) -> c_int {
@@ -331,19 +1017,20 @@ unsafe extern "C" fn f_sleep(state: *mut lua_State) -> c_int {
 }
 
 unsafe extern "C" fn f_exec(state: *mut lua_State) -> c_int {
-    let mut len = 0;
-    let cmd = luaL_checklstring(state, 1, &mut len);
-    let cmd = CStr::from_ptr(cmd).to_str().unwrap();
-    let buf = format!("{} &\0", cmd);
+    let cmd = checkbytes(state, 1);
+    // Running only part of a command could do something else entirely.
+    if cmd.contains(&0) {
+        return luaL_argerror(state, 1, c_str!("command contains a NUL byte"));
+    }
+    let mut buf = cmd.to_vec();
+    buf.extend_from_slice(b" &\0");
     let _ = system(buf.as_ptr() as *const c_char);
     0
 }
 
 unsafe extern "C" fn f_fuzzy_match(state: *mut lua_State) -> c_int {
-    let str = luaL_checklstring(state, 1, ptr::null_mut());
-    let str = CStr::from_ptr(str).to_str().unwrap();
-    let ptn = luaL_checklstring(state, 2, ptr::null_mut());
-    let ptn = CStr::from_ptr(ptn).to_str().unwrap();
+    let str = checkstring(state, 1);
+    let ptn = checkstring(state, 2);
     let mut score = 0;
     let mut run = 0;
 
@@ -371,7 +1058,7 @@ unsafe extern "C" fn f_fuzzy_match(state: *mut lua_State) -> c_int {
     1
 }
 
-static mut LIB: [luaL_Reg; 19] = [
+static mut LIB: [luaL_Reg; 43] = [
     luaL_Reg {
         name: c_str!("poll_event"),
         func: Some(f_poll_event),
@@ -384,6 +1071,10 @@ static mut LIB: [luaL_Reg; 19] = [
         name: c_str!("set_cursor"),
         func: Some(f_set_cursor),
     },
+    luaL_Reg {
+        name: c_str!("create_cursor"),
+        func: Some(f_create_cursor),
+    },
     luaL_Reg {
         name: c_str!("set_window_title"),
         func: Some(f_set_window_title),
@@ -392,6 +1083,38 @@ static mut LIB: [luaL_Reg; 19] = [
         name: c_str!("set_window_mode"),
         func: Some(f_set_window_mode),
     },
+    luaL_Reg {
+        name: c_str!("get_window_mode"),
+        func: Some(f_get_window_mode),
+    },
+    luaL_Reg {
+        name: c_str!("get_window_geometry"),
+        func: Some(f_get_window_geometry),
+    },
+    luaL_Reg {
+        name: c_str!("set_window_geometry"),
+        func: Some(f_set_window_geometry),
+    },
+    luaL_Reg {
+        name: c_str!("set_window_hit_regions"),
+        func: Some(f_set_window_hit_regions),
+    },
+    luaL_Reg {
+        name: c_str!("set_window_opacity"),
+        func: Some(f_set_window_opacity),
+    },
+    luaL_Reg {
+        name: c_str!("set_window_always_on_top"),
+        func: Some(f_set_window_always_on_top),
+    },
+    luaL_Reg {
+        name: c_str!("flash_window"),
+        func: Some(f_flash_window),
+    },
+    luaL_Reg {
+        name: c_str!("get_displays"),
+        func: Some(f_get_displays),
+    },
     luaL_Reg {
         name: c_str!("window_has_focus"),
         func: Some(f_window_has_focus),
@@ -412,6 +1135,58 @@ static mut LIB: [luaL_Reg; 19] = [
         name: c_str!("list_dir"),
         func: Some(f_list_dir),
     },
+    luaL_Reg {
+        name: c_str!("mkdir"),
+        func: Some(f_mkdir),
+    },
+    luaL_Reg {
+        name: c_str!("rmdir"),
+        func: Some(f_rmdir),
+    },
+    luaL_Reg {
+        name: c_str!("remove"),
+        func: Some(f_remove),
+    },
+    luaL_Reg {
+        name: c_str!("rename"),
+        func: Some(f_rename),
+    },
+    luaL_Reg {
+        name: c_str!("copy"),
+        func: Some(f_copy),
+    },
+    luaL_Reg {
+        name: c_str!("chmod"),
+        func: Some(f_chmod),
+    },
+    luaL_Reg {
+        name: c_str!("write_file_atomic"),
+        func: Some(f_write_file_atomic),
+    },
+    luaL_Reg {
+        name: c_str!("detect_encoding"),
+        func: Some(f_detect_encoding),
+    },
+    luaL_Reg {
+        name: c_str!("convert_encoding"),
+        func: Some(f_convert_encoding),
+    },
+    luaL_Reg {
+        name: c_str!("analyze_text"),
+        func: Some(f_analyze_text),
+    },
+    luaL_Reg {
+        name: c_str!("trash"),
+        func: Some(f_trash),
+    },
+    luaL_Reg {
+        name: c_str!("list_trash"),
+        func: Some(f_list_trash),
+    },
+    luaL_Reg {
+        name: c_str!("restore_from_trash"),
+        func: Some(f_restore_from_trash),
+    },
     luaL_Reg {
         name: c_str!("absolute_path"),
         func: Some(f_absolute_path),
@@ -420,6 +1195,10 @@ static mut LIB: [luaL_Reg; 19] = [
         name: c_str!("get_file_info"),
         func: Some(f_get_file_info),
     },
+    luaL_Reg {
+        name: c_str!("list_fonts"),
+        func: Some(f_list_fonts),
+    },
     luaL_Reg {
         name: c_str!("get_clipboard"),
         func: Some(f_get_clipboard),
@@ -428,6 +1207,10 @@ static mut LIB: [luaL_Reg; 19] = [
         name: c_str!("set_clipboard"),
         func: Some(f_set_clipboard),
     },
+    luaL_Reg {
+        name: c_str!("has_clipboard_text"),
+        func: Some(f_has_clipboard_text),
+    },
     luaL_Reg {
         name: c_str!("get_time"),
         func: Some(f_get_time),
@@ -454,10 +1237,14 @@ pub(super) unsafe extern "C" fn luaopen_system(state: *mut lua_State) -> c_int {
     lua_createtable(
         state,
         0,
-        mem::size_of::<[luaL_Reg; 19]>()
+        mem::size_of::<[luaL_Reg; 43]>()
             .wrapping_div(mem::size_of::<luaL_Reg>())
             .wrapping_sub(1) as c_int,
     );
     luaL_setfuncs(state, LIB.as_ptr(), 0);
+    luaopen_system_window(state);
+    lua_setfield(state, -2, c_str!("window"));
+    luaopen_system_large_file(state);
+    lua_setfield(state, -2, c_str!("large_file"));
     1
 }
@@ -0,0 +1,175 @@
+use crate::{
+    api::{checkbytes, checkpath, push_bytes, push_string},
+    c_str,
+    largefile::{LargeFile, Search},
+};
+use lua_sys::*;
+use std::{mem, os::raw::c_int, path::Path, ptr};
+
+/// How many bytes of a line are returned when no length is given.
+const LINE_PART: lua_Integer = 64 << 10;
+
+unsafe fn checkfile<'a>(state: *mut lua_State, idx: c_int) -> &'a LargeFile {
+    let self_0 = luaL_checkudata(state, idx, c_str!("LargeFile")) as *mut *mut LargeFile;
+    if (*self_0).is_null() {
+        luaL_error(state, c_str!("file is closed"));
+    }
+    &**self_0
+}
+
+unsafe fn close(self_0: *mut *mut LargeFile) {
+    if !(*self_0).is_null() {
+        drop(Box::from_raw(*self_0));
+        *self_0 = ptr::null_mut();
+    }
+}
+
+unsafe extern "C" fn f_open(state: *mut lua_State) -> c_int {
+    let path = checkpath(state, 1);
+    match LargeFile::open(Path::new(&path)) {
+        Ok(file) => {
+            let self_0 =
+                lua_newuserdata(state, mem::size_of::<*mut LargeFile>()) as *mut *mut LargeFile;
+            *self_0 = Box::into_raw(Box::new(file));
+            luaL_setmetatable(state, c_str!("LargeFile"));
+            1
+        }
+        Err(error) => {
+            lua_pushnil(state);
+            push_string(state, &error.to_string());
+            2
+        }
+    }
+}
+
+unsafe extern "C" fn f_close(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("LargeFile")) as *mut *mut LargeFile;
+    close(self_0);
+    0
+}
+
+unsafe extern "C" fn f_gc(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("LargeFile")) as *mut *mut LargeFile;
+    close(self_0);
+    0
+}
+
+unsafe extern "C" fn f_get_size(state: *mut lua_State) -> c_int {
+    let file = checkfile(state, 1);
+    lua_pushnumber(state, file.len() as lua_Number);
+    1
+}
+
+unsafe extern "C" fn f_get_line_count(state: *mut lua_State) -> c_int {
+    let file = checkfile(state, 1);
+    // The index may complete in between, so it is checked first.
+    let indexed = file.is_indexed();
+    lua_pushinteger(state, file.line_count() as lua_Integer);
+    lua_pushboolean(state, indexed as c_int);
+    2
+}
+
+/// Returns the bytes of a line from a column, at most `LINE_PART` of them
+/// unless a length is given, or nothing if the line is not indexed yet.
+unsafe extern "C" fn f_get_line(state: *mut lua_State) -> c_int {
+    let file = checkfile(state, 1);
+    let line = luaL_checkinteger(state, 2);
+    let col = luaL_optinteger(state, 3, 1).max(1) as usize;
+    let len = luaL_optinteger(state, 4, LINE_PART).max(0) as usize;
+    if line < 1 {
+        return 0;
+    }
+    let start = col - 1;
+    match file.line(line as usize - 1, start..start.saturating_add(len)) {
+        Some(text) => {
+            push_bytes(state, &text);
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Returns the line and the column where the text is found, nothing if it is
+/// not, or `nil` followed by the line and the column to go on from when the
+/// `limit` option, a number of bytes, stopped the search before the end.
+unsafe extern "C" fn f_find(state: *mut lua_State) -> c_int {
+    let file = checkfile(state, 1);
+    let text = checkbytes(state, 2);
+    let line = luaL_optinteger(state, 3, 1).max(1) as usize;
+    let col = luaL_optinteger(state, 4, 1).max(1) as usize;
+    let (no_case, limit) = if lua_isnoneornil(state, 5) != 0 {
+        (false, u64::MAX)
+    } else {
+        luaL_checktype(state, 5, LUA_TTABLE);
+        lua_getfield(state, 5, c_str!("no_case"));
+        let no_case = lua_toboolean(state, -1) != 0;
+        lua_getfield(state, 5, c_str!("limit"));
+        let limit = match lua_isnoneornil(state, -1) {
+            0 => luaL_checknumber(state, -1).max(1.0) as u64,
+            _ => u64::MAX,
+        };
+        lua_settop(state, -3);
+        (no_case, limit)
+    };
+    match file.find(text, line - 1, col - 1, no_case, limit) {
+        Ok(Search::Found(line, col)) => {
+            lua_pushinteger(state, line as lua_Integer + 1);
+            lua_pushinteger(state, col as lua_Integer + 1);
+            2
+        }
+        Ok(Search::NotFound) => 0,
+        Ok(Search::Stopped(line, col)) => {
+            lua_pushnil(state);
+            lua_pushinteger(state, line as lua_Integer + 1);
+            lua_pushinteger(state, col as lua_Integer + 1);
+            3
+        }
+        Err(error) => {
+            push_string(state, &error.to_string());
+            lua_error(state)
+        }
+    }
+}
+
+static mut LIB: [luaL_Reg; 8] = [
+    luaL_Reg {
+        name: c_str!("__gc"),
+        func: Some(f_gc),
+    },
+    luaL_Reg {
+        name: c_str!("open"),
+        func: Some(f_open),
+    },
+    luaL_Reg {
+        name: c_str!("close"),
+        func: Some(f_close),
+    },
+    luaL_Reg {
+        name: c_str!("get_size"),
+        func: Some(f_get_size),
+    },
+    luaL_Reg {
+        name: c_str!("get_line_count"),
+        func: Some(f_get_line_count),
+    },
+    luaL_Reg {
+        name: c_str!("get_line"),
+        func: Some(f_get_line),
+    },
+    luaL_Reg {
+        name: c_str!("find"),
+        func: Some(f_find),
+    },
+    luaL_Reg {
+        name: ptr::null(),
+        func: None,
+    },
+];
+
+pub(super) unsafe fn luaopen_system_large_file(state: *mut lua_State) -> c_int {
+    luaL_newmetatable(state, c_str!("LargeFile"));
+    luaL_setfuncs(state, ptr::addr_of!(LIB).cast(), 0);
+    lua_pushvalue(state, -1);
+    lua_setfield(state, -2, c_str!("__index"));
+    1
+}
@@ -0,0 +1,303 @@
+use crate::{
+    api::{
+        checkstring, optstring, push_string,
+        renderer::RENDER_TARGET,
+        system::{
+            checkgeometry, checkhitregions, push_event, push_geometry, FLASH_OPTS, WINDOW_OPTS,
+        },
+        until_nul,
+    },
+    c_str,
+    rencache::RenCache,
+    window::{Event, FlashMode, Window, WindowMode},
+    WINDOW,
+};
+use lua_sys::*;
+use std::{mem, os::raw::c_int, ptr};
+
+/// A window opened from Lua, along with the render cache used to draw into it.
+pub(super) struct WindowTarget {
+    pub(super) window: Window,
+    pub(super) rencache: RenCache,
+}
+
+unsafe fn checkwindow(state: *mut lua_State, idx: c_int) -> *mut WindowTarget {
+    let self_0 = luaL_checkudata(state, idx, c_str!("Window")) as *mut *mut WindowTarget;
+    if (*self_0).is_null() {
+        luaL_error(state, c_str!("window is closed"));
+    }
+    *self_0
+}
+
+unsafe fn close(self_0: *mut *mut WindowTarget) {
+    if !(*self_0).is_null() {
+        if RENDER_TARGET == *self_0 {
+            RENDER_TARGET = ptr::null_mut();
+        }
+        drop(Box::from_raw(*self_0));
+        *self_0 = ptr::null_mut();
+    }
+}
+
+unsafe extern "C" fn f_create(state: *mut lua_State) -> c_int {
+    let title = optstring(state, 1, "");
+    let width = luaL_checknumber(state, 2) as u32;
+    let height = luaL_checknumber(state, 3) as u32;
+    let borderless = if lua_isnoneornil(state, 4) != 0 {
+        false
+    } else {
+        luaL_checktype(state, 4, LUA_TTABLE);
+        lua_getfield(state, 4, c_str!("borderless"));
+        let borderless = lua_toboolean(state, -1) != 0;
+        lua_settop(state, -2);
+        borderless
+    };
+    let self_0 =
+        lua_newuserdata(state, mem::size_of::<*mut WindowTarget>()) as *mut *mut WindowTarget;
+    *self_0 = ptr::null_mut();
+    luaL_setmetatable(state, c_str!("Window"));
+    let main_window = (*ptr::addr_of!(WINDOW)).lock().unwrap();
+    let window = main_window.open(until_nul(&title), width, height, borderless);
+    drop(main_window);
+    match window {
+        Ok(window) => {
+            let rencache = RenCache::init(&window);
+            *self_0 = Box::into_raw(Box::new(WindowTarget { window, rencache }));
+        }
+        Err(_) => {
+            luaL_error(state, c_str!("failed to create window"));
+        }
+    }
+    1
+}
+
+unsafe extern "C" fn f_close(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Window")) as *mut *mut WindowTarget;
+    close(self_0);
+    0
+}
+
+unsafe extern "C" fn f_gc(state: *mut lua_State) -> c_int {
+    let self_0 = luaL_checkudata(state, 1, c_str!("Window")) as *mut *mut WindowTarget;
+    close(self_0);
+    0
+}
+
+unsafe extern "C" fn f_get_id(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    lua_pushnumber(state, (*target).window.id() as lua_Number);
+    1
+}
+
+/// Polls the events of this window only, the events of the main window are
+/// polled by `system.poll_event`.
+unsafe extern "C" fn f_poll_event(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    match (*target).window.poll_event() {
+        Option::None => 0,
+        Some(event) => {
+            if let Event::Exposed = event {
+                (*target).rencache.invalidate();
+            }
+            push_event(state, event)
+        }
+    }
+}
+
+unsafe extern "C" fn f_set_title(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let title = checkstring(state, 2);
+    (*target).window.set_title(until_nul(&title));
+    0
+}
+
+unsafe extern "C" fn f_set_mode(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let n = luaL_checkoption(
+        state,
+        2,
+        c_str!("normal"),
+        ptr::addr_of!(WINDOW_OPTS).cast(),
+    );
+    (*target).window.set_mode(WindowMode::from_raw(n));
+    0
+}
+
+unsafe extern "C" fn f_get_mode(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    push_string(state, (*target).window.mode().name());
+    1
+}
+
+unsafe extern "C" fn f_get_geometry(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    push_geometry(state, (*target).window.geometry())
+}
+
+unsafe extern "C" fn f_set_geometry(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let geometry = checkgeometry(state, 2);
+    if let Err(error) = (*target).window.set_geometry(geometry) {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_set_hit_regions(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let regions = checkhitregions(state, 2);
+    if let Err(error) = (*target).window.set_hit_regions(regions) {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_set_opacity(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let opacity = luaL_checknumber(state, 2) as f32;
+    if let Err(error) = (*target).window.set_opacity(opacity) {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_set_always_on_top(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let on_top = lua_toboolean(state, 2) != 0;
+    if let Err(error) = (*target).window.set_always_on_top(on_top) {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_flash(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let n = luaL_checkoption(
+        state,
+        2,
+        c_str!("briefly"),
+        ptr::addr_of!(FLASH_OPTS).cast(),
+    );
+    if let Err(error) = (*target).window.flash(FlashMode::from_raw(n)) {
+        lua_pushnil(state);
+        push_string(state, &error);
+        return 2;
+    }
+    lua_pushboolean(state, 1);
+    1
+}
+
+unsafe extern "C" fn f_has_focus(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    lua_pushboolean(state, (*target).window.has_focus() as c_int);
+    1
+}
+
+unsafe extern "C" fn f_get_size(state: *mut lua_State) -> c_int {
+    let target = checkwindow(state, 1);
+    let (w, h) = (*target).window.size();
+    lua_pushnumber(state, w as lua_Number);
+    lua_pushnumber(state, h as lua_Number);
+    2
+}
+
+unsafe extern "C" fn f_get_main_id(state: *mut lua_State) -> c_int {
+    let id = (*ptr::addr_of!(WINDOW)).lock().unwrap().id();
+    lua_pushnumber(state, id as lua_Number);
+    1
+}
+
+static mut LIB: [luaL_Reg; 18] = [
+    luaL_Reg {
+        name: c_str!("__gc"),
+        func: Some(f_gc),
+    },
+    luaL_Reg {
+        name: c_str!("create"),
+        func: Some(f_create),
+    },
+    luaL_Reg {
+        name: c_str!("close"),
+        func: Some(f_close),
+    },
+    luaL_Reg {
+        name: c_str!("get_id"),
+        func: Some(f_get_id),
+    },
+    luaL_Reg {
+        name: c_str!("poll_event"),
+        func: Some(f_poll_event),
+    },
+    luaL_Reg {
+        name: c_str!("set_title"),
+        func: Some(f_set_title),
+    },
+    luaL_Reg {
+        name: c_str!("set_mode"),
+        func: Some(f_set_mode),
+    },
+    luaL_Reg {
+        name: c_str!("get_mode"),
+        func: Some(f_get_mode),
+    },
+    luaL_Reg {
+        name: c_str!("get_geometry"),
+        func: Some(f_get_geometry),
+    },
+    luaL_Reg {
+        name: c_str!("set_geometry"),
+        func: Some(f_set_geometry),
+    },
+    luaL_Reg {
+        name: c_str!("set_hit_regions"),
+        func: Some(f_set_hit_regions),
+    },
+    luaL_Reg {
+        name: c_str!("set_opacity"),
+        func: Some(f_set_opacity),
+    },
+    luaL_Reg {
+        name: c_str!("set_always_on_top"),
+        func: Some(f_set_always_on_top),
+    },
+    luaL_Reg {
+        name: c_str!("flash"),
+        func: Some(f_flash),
+    },
+    luaL_Reg {
+        name: c_str!("has_focus"),
+        func: Some(f_has_focus),
+    },
+    luaL_Reg {
+        name: c_str!("get_size"),
+        func: Some(f_get_size),
+    },
+    luaL_Reg {
+        name: c_str!("get_main_id"),
+        func: Some(f_get_main_id),
+    },
+    luaL_Reg {
+        name: ptr::null(),
+        func: None,
+    },
+];
+
+pub(super) unsafe fn luaopen_system_window(state: *mut lua_State) -> c_int {
+    luaL_newmetatable(state, c_str!("Window"));
+    luaL_setfuncs(state, ptr::addr_of!(LIB).cast(), 0);
+    lua_pushvalue(state, -1);
+    lua_setfield(state, -2, c_str!("__index"));
+    1
+}
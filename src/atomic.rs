@@ -0,0 +1,275 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Creates a file next to `path` to write into before renaming it.
+fn create_temp_file(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Cannot write to this path"))?;
+    for n in 0.. {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(".{}-{}.tmp", process::id(), n));
+        let temp_path = path.with_file_name(temp_name);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path);
+        match file {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(unix)]
+fn copy_owner(file: &File, metadata: &fs::Metadata) {
+    use std::os::unix::{fs::MetadataExt, io::AsRawFd};
+
+    // Changing the owner usually needs privileges, the file is owned by the
+    // user saving it otherwise.
+    unsafe { libc::fchown(file.as_raw_fd(), metadata.uid(), metadata.gid()) };
+}
+
+#[cfg(windows)]
+fn copy_owner(_file: &File, _metadata: &fs::Metadata) {}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(windows)]
+fn sync_dir(_dir: &Path) {}
+
+/// Where a copy of the file being replaced is made.
+pub(super) enum Backup {
+    /// Next to the file, with "~" appended to its name. This is next to the
+    /// target of a symlink.
+    Tilde,
+    Path(PathBuf),
+}
+
+/// How a file was written.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) enum Written {
+    /// Through a temporary file renamed over the original.
+    Atomically,
+    /// Over the original, which was left partially written if this failed.
+    InPlace,
+}
+
+fn copy_backup(path: &Path, backup: &Path) -> io::Result<()> {
+    match fs::copy(path, backup) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(io::Error::new(
+            error.kind(),
+            format!("Could not back up to {}: {}", backup.display(), error),
+        )),
+    }
+}
+
+/// Overwrites a file, for when no temporary file can be created next to it.
+fn write_in_place(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Replaces the content of a file without ever leaving it partially written:
+/// the data is written to a temporary file in the same directory which is then
+/// renamed over the original. The permissions and the owner of the original
+/// are kept, and a copy of it is made if `backup` is given. A writable file in
+/// a directory which is not is overwritten in place instead, which is returned
+/// so that the user can be told.
+pub(super) fn write_file_atomic(
+    path: &Path,
+    data: &[u8],
+    backup: Option<Backup>,
+) -> io::Result<Written> {
+    // Symlinks are kept, their target is written instead.
+    let path = match fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(error) => return Err(error),
+    };
+    let original = fs::metadata(&path).ok();
+    let backup = backup.map(|backup| match backup {
+        Backup::Tilde => {
+            let mut backup = path.clone().into_os_string();
+            backup.push("~");
+            PathBuf::from(backup)
+        }
+        Backup::Path(backup) => backup,
+    });
+
+    let (temp_path, mut file) = match create_temp_file(&path) {
+        Ok(temp) => temp,
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied && original.is_some() => {
+            if let Some(backup) = &backup {
+                copy_backup(&path, backup)?;
+            }
+            return write_in_place(&path, data).map(|_| Written::InPlace);
+        }
+        Err(error) => return Err(error),
+    };
+    let result = file.write_all(data).and_then(|_| {
+        if let Some(original) = &original {
+            copy_owner(&file, original);
+            fs::set_permissions(&temp_path, original.permissions())?;
+        }
+        file.sync_all()
+    });
+    drop(file);
+    let result = result.and_then(|_| {
+        if let (Some(backup), Some(_)) = (&backup, &original) {
+            copy_backup(&path, backup)?;
+        }
+        fs::rename(&temp_path, &path)
+    });
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error);
+    }
+    if let Some(dir) = path.parent() {
+        sync_dir(dir);
+    }
+    Ok(Written::Atomically)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Runs a test in a new temporary directory, removed afterwards.
+    fn with_dir(name: &str, test: impl FnOnce(&Path)) {
+        let dir = env::temp_dir().join(format!("atomic-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        test(&fs::canonicalize(&dir).unwrap());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn file_names(dir: &Path) -> Vec<OsString> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn create_and_replace() {
+        with_dir("replace", |dir| {
+            let path = dir.join("file");
+            write_file_atomic(&path, b"first", None).unwrap();
+            assert_eq!(fs::read(&path).unwrap(), b"first");
+            let written = write_file_atomic(&path, b"second", None).unwrap();
+            assert_eq!(written, Written::Atomically);
+            assert_eq!(fs::read(&path).unwrap(), b"second");
+            // No temporary file is left behind.
+            assert_eq!(file_names(dir), ["file"]);
+        });
+    }
+
+    #[test]
+    fn backup() {
+        with_dir("backup", |dir| {
+            let path = dir.join("file");
+            write_file_atomic(&path, b"first", Some(Backup::Tilde)).unwrap();
+            // There is nothing to back up yet.
+            assert_eq!(file_names(dir), ["file"]);
+            write_file_atomic(&path, b"second", Some(Backup::Tilde)).unwrap();
+            assert_eq!(fs::read(dir.join("file~")).unwrap(), b"first");
+
+            let backup = dir.join("backup");
+            write_file_atomic(&path, b"third", Some(Backup::Path(backup.clone()))).unwrap();
+            assert_eq!(fs::read(backup).unwrap(), b"second");
+            assert_eq!(fs::read(&path).unwrap(), b"third");
+
+            // The file is left alone when it cannot be backed up.
+            let backup = dir.join("missing/backup");
+            let error =
+                write_file_atomic(&path, b"fourth", Some(Backup::Path(backup))).unwrap_err();
+            assert!(error.to_string().starts_with("Could not back up to "));
+            assert_eq!(fs::read(&path).unwrap(), b"third");
+        });
+    }
+
+    #[test]
+    fn invalid_path() {
+        with_dir("invalid", |dir| {
+            assert!(write_file_atomic(&dir.join("missing/file"), b"", None).is_err());
+            assert!(write_file_atomic(Path::new("/"), b"", None).is_err());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        with_dir("permissions", |dir| {
+            let path = dir.join("script");
+            fs::write(&path, "old").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+            write_file_atomic(&path, b"new", None).unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o751);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_through_symlink() {
+        with_dir("symlink", |dir| {
+            let target = dir.join("target");
+            let link = dir.join("link");
+            fs::write(&target, "old").unwrap();
+            std::os::unix::fs::symlink("target", &link).unwrap();
+            write_file_atomic(&link, b"new", Some(Backup::Tilde)).unwrap();
+            assert!(fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert_eq!(fs::read(&target).unwrap(), b"new");
+            // The backup is made next to the file which was written.
+            assert_eq!(fs::read(dir.join("target~")).unwrap(), b"old");
+            assert!(!dir.join("link~").exists());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        with_dir("read-only", |dir| {
+            let path = dir.join("file");
+            fs::write(&path, "old").unwrap();
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o555)).unwrap();
+            let result = write_file_atomic(&path, b"new", None);
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o755)).unwrap();
+            // Permissions do not apply to root, which can still rename.
+            let expected = if unsafe { libc::geteuid() } == 0 {
+                Written::Atomically
+            } else {
+                Written::InPlace
+            };
+            assert_eq!(result.unwrap(), expected);
+            assert_eq!(fs::read(&path).unwrap(), b"new");
+            assert_eq!(file_names(dir), ["file"]);
+        });
+    }
+}
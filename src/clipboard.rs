@@ -0,0 +1,187 @@
+use sdl2::sys::{
+    SDL_GetClipboardText, SDL_HasClipboardText, SDL_SetClipboardText, SDL_bool, SDL_free,
+};
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_int},
+};
+
+/// The selections text can be exchanged through. The primary selection holds
+/// the last selected text, it only exists on X11 and Wayland.
+#[derive(Copy, Clone, PartialEq)]
+pub(super) enum Selection {
+    Clipboard,
+    Primary,
+}
+
+type GetText = unsafe extern "C" fn() -> *mut c_char;
+type SetText = unsafe extern "C" fn(*const c_char) -> c_int;
+type HasText = unsafe extern "C" fn() -> SDL_bool;
+
+struct SelectionFunctions {
+    get: GetText,
+    set: SetText,
+    has: HasText,
+}
+
+static CLIPBOARD: SelectionFunctions = SelectionFunctions {
+    get: SDL_GetClipboardText,
+    set: SDL_SetClipboardText,
+    has: SDL_HasClipboardText,
+};
+
+/// Returns the primary selection functions, which are looked up at runtime
+/// since they only exist from SDL 2.26.
+#[cfg(unix)]
+fn primary_selection() -> Option<&'static SelectionFunctions> {
+    use crate::window::sdl_function as lookup;
+    use once_cell::sync::Lazy;
+    use std::{mem, os::raw::c_void};
+
+    static PRIMARY_SELECTION: Lazy<Option<SelectionFunctions>> = Lazy::new(|| unsafe {
+        Some(SelectionFunctions {
+            get: mem::transmute::<*mut c_void, GetText>(lookup(b"SDL_GetPrimarySelectionText\0")?),
+            set: mem::transmute::<*mut c_void, SetText>(lookup(b"SDL_SetPrimarySelectionText\0")?),
+            has: mem::transmute::<*mut c_void, HasText>(lookup(b"SDL_HasPrimarySelectionText\0")?),
+        })
+    });
+    PRIMARY_SELECTION.as_ref()
+}
+
+#[cfg(not(unix))]
+fn primary_selection() -> Option<&'static SelectionFunctions> {
+    None
+}
+
+fn functions(selection: Selection) -> Result<&'static SelectionFunctions, String> {
+    match selection {
+        Selection::Clipboard => Ok(&CLIPBOARD),
+        Selection::Primary => {
+            primary_selection().ok_or_else(|| "The primary selection is not supported".to_string())
+        }
+    }
+}
+
+/// Returns the text of a selection, invalid UTF-8 sequences are replaced by
+/// the replacement character.
+pub(super) fn get_text(selection: Selection) -> Result<String, String> {
+    let functions = functions(selection)?;
+    unsafe {
+        let buf = (functions.get)();
+        if buf.is_null() {
+            return Err(sdl2::get_error());
+        }
+        let text = CStr::from_ptr(buf).to_string_lossy().into_owned();
+        SDL_free(buf as *mut _);
+        Ok(text)
+    }
+}
+
+pub(super) fn has_text(selection: Selection) -> bool {
+    match functions(selection) {
+        Ok(functions) => unsafe { (functions.has)() == SDL_bool::SDL_TRUE },
+        Err(_) => false,
+    }
+}
+
+/// Sets the text of a selection, which ends at the first NUL byte. An HTML
+/// version of the text is also offered to the applications which can paste it
+/// where the platform allows it, returns whether it was.
+pub(super) fn set_text(
+    selection: Selection,
+    text: &str,
+    html: Option<&str>,
+) -> Result<bool, String> {
+    let functions = functions(selection)?;
+    let mut text = text.as_bytes().to_vec();
+    text.push(0);
+    if unsafe { (functions.set)(text.as_ptr() as *const c_char) } != 0 {
+        return Err(sdl2::get_error());
+    }
+    match (selection, html) {
+        (Selection::Clipboard, Some(html)) => Ok(unsafe { offer_html(html) }),
+        _ => Ok(false),
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+    pub(super) const GMEM_MOVEABLE: c_uint = 0x0002;
+
+    #[link(name = "user32")]
+    extern "system" {
+        pub(super) fn RegisterClipboardFormatA(name: *const c_char) -> c_uint;
+        pub(super) fn OpenClipboard(owner: *mut c_void) -> c_int;
+        pub(super) fn CloseClipboard() -> c_int;
+        pub(super) fn SetClipboardData(format: c_uint, mem: *mut c_void) -> *mut c_void;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub(super) fn GlobalAlloc(flags: c_uint, bytes: usize) -> *mut c_void;
+        pub(super) fn GlobalLock(mem: *mut c_void) -> *mut c_void;
+        pub(super) fn GlobalUnlock(mem: *mut c_void) -> c_int;
+        pub(super) fn GlobalFree(mem: *mut c_void) -> *mut c_void;
+    }
+}
+
+/// Wraps an HTML fragment into the "HTML Format" of the Windows clipboard,
+/// whose header gives the byte offsets of the document and of the fragment.
+#[cfg(windows)]
+fn cf_html(fragment: &str) -> String {
+    const PREFIX: &str = "<html><body><!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment--></body></html>";
+    let header = |offsets: [usize; 4]| {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            offsets[0], offsets[1], offsets[2], offsets[3]
+        )
+    };
+    let start_html = header([0; 4]).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+    let mut data = header([start_html, end_html, start_fragment, end_fragment]);
+    data.push_str(PREFIX);
+    data.push_str(fragment);
+    data.push_str(SUFFIX);
+    data
+}
+
+/// Adds HTML to the text SDL just put into the clipboard.
+#[cfg(windows)]
+unsafe fn offer_html(fragment: &str) -> bool {
+    use std::ptr;
+    use win32::*;
+
+    let data = cf_html(fragment);
+    let format = RegisterClipboardFormatA(b"HTML Format\0".as_ptr() as *const c_char);
+    if format == 0 || OpenClipboard(ptr::null_mut()) == 0 {
+        return false;
+    }
+    let mut offered = false;
+    let mem = GlobalAlloc(GMEM_MOVEABLE, data.len() + 1);
+    if !mem.is_null() {
+        let dest = GlobalLock(mem) as *mut u8;
+        if !dest.is_null() {
+            ptr::copy_nonoverlapping(data.as_ptr(), dest, data.len());
+            *dest.add(data.len()) = 0;
+            GlobalUnlock(mem);
+            // The clipboard owns the memory once it has been set.
+            offered = !SetClipboardData(format, mem).is_null();
+        }
+        if !offered {
+            GlobalFree(mem);
+        }
+    }
+    CloseClipboard();
+    offered
+}
+
+/// SDL only exchanges plain text on the other platforms.
+#[cfg(not(windows))]
+unsafe fn offer_html(_fragment: &str) -> bool {
+    false
+}
@@ -0,0 +1,193 @@
+use std::{fs, io, path::Path};
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    // Windows links to directories differ from the ones to files.
+    let target = fs::read_link(from)?;
+    match fs::metadata(from) {
+        Ok(metadata) if metadata.is_dir() => symlink_dir(target, to),
+        _ => symlink_file(target, to),
+    }
+}
+
+/// Whether both paths lead to the same file, including through links.
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (a, b) = match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => return Ok(false),
+    };
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+/// Whether both paths lead to the same file, including through links.
+#[cfg(windows)]
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => Ok(a == b),
+        _ => Ok(false),
+    }
+}
+
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+    if file_type.is_symlink() {
+        copy_symlink(from, to)
+    } else if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Copies a file, or a directory along with its content. Symbolic links are
+/// copied as links, and what was copied is removed if the copy fails.
+pub(super) fn copy_path(from: &Path, to: &Path) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+    if file_type.is_file() && same_file(from, to)? {
+        // Copying would truncate the source before reading it.
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Cannot copy a file onto itself",
+        ));
+    }
+    if file_type.is_dir() {
+        // The destination does not exist yet, so its parent is resolved.
+        let parent = match to.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        let mut dest = fs::canonicalize(parent)?;
+        dest.extend(to.file_name());
+        if dest.starts_with(fs::canonicalize(from)?) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot copy a directory into itself",
+            ));
+        }
+    }
+    let existed = fs::symlink_metadata(to).is_ok();
+    let result = copy_tree(from, to);
+    if result.is_err() && !existed {
+        let _ = remove_path(to);
+    }
+    result
+}
+
+/// Removes a file, or a directory along with its content.
+pub(super) fn remove_path(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Runs a test in a new temporary directory, removed afterwards.
+    fn with_dir(name: &str, test: impl FnOnce(&Path)) {
+        let dir = env::temp_dir().join(format!("copy-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        test(&fs::canonicalize(&dir).unwrap());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn copy_onto_itself() {
+        with_dir("itself", |dir| {
+            let file = dir.join("file");
+            fs::write(&file, "content").unwrap();
+            let error = copy_path(&file, &dir.join(".").join("file")).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+
+            let subdir = dir.join("dir");
+            fs::create_dir(&subdir).unwrap();
+            let error = copy_path(&subdir, &subdir.join("copy")).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert!(!subdir.join("copy").exists());
+        });
+    }
+
+    #[test]
+    fn copy_onto_link() {
+        with_dir("link", |dir| {
+            let file = dir.join("file");
+            fs::write(&file, "content").unwrap();
+            let hard_link = dir.join("hard");
+            fs::hard_link(&file, &hard_link).unwrap();
+            let error = copy_path(&file, &hard_link).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            #[cfg(unix)]
+            {
+                let link = dir.join("link");
+                std::os::unix::fs::symlink(&file, &link).unwrap();
+                let error = copy_path(&file, &link).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            }
+            assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_symlinks() {
+        with_dir("symlinks", |dir| {
+            let from = dir.join("from");
+            fs::create_dir(&from).unwrap();
+            fs::write(from.join("file"), "content").unwrap();
+            std::os::unix::fs::symlink("file", from.join("link")).unwrap();
+            std::os::unix::fs::symlink("missing", from.join("broken")).unwrap();
+
+            let to = dir.join("to");
+            copy_path(&from, &to).unwrap();
+            assert_eq!(fs::read_to_string(to.join("file")).unwrap(), "content");
+            for name in &["link", "broken"] {
+                let link = to.join(name);
+                assert!(fs::symlink_metadata(&link)
+                    .unwrap()
+                    .file_type()
+                    .is_symlink());
+                assert_eq!(
+                    fs::read_link(&link).unwrap(),
+                    fs::read_link(from.join(name)).unwrap()
+                );
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_partial_copy() {
+        with_dir("partial", |dir| {
+            let from = dir.join("from");
+            fs::create_dir(&from).unwrap();
+            fs::write(from.join("file"), "content").unwrap();
+            // Sockets cannot be opened, so copying one always fails.
+            let _socket = std::os::unix::net::UnixListener::bind(from.join("socket")).unwrap();
+
+            let to = dir.join("to");
+            assert!(copy_path(&from, &to).is_err());
+            assert!(fs::symlink_metadata(&to).is_err());
+        });
+    }
+}
@@ -0,0 +1,305 @@
+use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+use std::{convert::TryFrom, str};
+
+#[derive(Copy, Clone, PartialEq)]
+pub(super) enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+    ShiftJis,
+}
+
+impl Encoding {
+    /// Accepts the name returned by `name` along with a few common aliases,
+    /// ignoring the case.
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "utf-16le" | "utf16le" => Some(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Self::Utf16Be),
+            "iso-8859-1" | "latin1" | "latin-1" => Some(Self::Latin1),
+            "windows-1252" | "cp1252" => Some(Self::Windows1252),
+            "shift_jis" | "shift-jis" | "sjis" => Some(Self::ShiftJis),
+            _ => None,
+        }
+    }
+
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "ISO-8859-1",
+            Self::Windows1252 => "windows-1252",
+            Self::ShiftJis => "Shift_JIS",
+        }
+    }
+
+    pub(super) fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => b"\xef\xbb\xbf",
+            Self::Utf16Le => b"\xff\xfe",
+            Self::Utf16Be => b"\xfe\xff",
+            _ => b"",
+        }
+    }
+}
+
+#[derive(Default)]
+struct SjisPairs {
+    /// Pairs whose second byte is not ASCII.
+    non_ascii: usize,
+    /// Pairs whose second byte is ASCII.
+    ascii: usize,
+    /// Non-ASCII bytes outside of any pair, apart from half-width katakana.
+    single_bytes: usize,
+}
+
+impl SjisPairs {
+    fn total(&self) -> usize {
+        self.non_ascii + self.ascii
+    }
+}
+
+/// Counts the bytes which form a Shift_JIS double-byte character, that is a
+/// lead byte followed by a valid trail byte, and the other non-ASCII bytes.
+fn count_sjis_pairs(data: &[u8]) -> SjisPairs {
+    let mut pairs = SjisPairs::default();
+    let mut i = 0;
+    while i < data.len() {
+        let lead = matches!(data[i], 0x81..=0x9f | 0xe0..=0xfc);
+        match data.get(i + 1) {
+            Some(0x40..=0x7e) if lead => pairs.ascii += 1,
+            Some(0x80..=0xfc) if lead => pairs.non_ascii += 1,
+            _ => {
+                if data[i] >= 0x80 && !(0xa1..=0xdf).contains(&data[i]) {
+                    pairs.single_bytes += 1;
+                }
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    pairs
+}
+
+/// Guesses the encoding of some text, returns it along with whether the text
+/// starts with a byte order mark.
+pub(super) fn detect(data: &[u8]) -> (Encoding, bool) {
+    for &encoding in &[Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+        if data.starts_with(encoding.bom()) {
+            return (encoding, true);
+        }
+    }
+    // Text in UTF-16 without a BOM is mostly ASCII, so every other byte is a
+    // zero. This is checked first since such text is also valid UTF-8.
+    let sample = &data[..data.len().min(4096) & !1];
+    let zeros_at = |parity| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&byte| byte == 0)
+            .count()
+    };
+    let pairs = sample.len() / 2;
+    if pairs > 0 {
+        if zeros_at(1) * 10 >= pairs * 3 && zeros_at(0) == 0 {
+            return (Encoding::Utf16Le, false);
+        }
+        if zeros_at(0) * 10 >= pairs * 3 && zeros_at(1) == 0 {
+            return (Encoding::Utf16Be, false);
+        }
+    }
+    if str::from_utf8(data).is_ok() {
+        return (Encoding::Utf8, false);
+    }
+    // Shift_JIS is only assumed when double-byte characters make up most of
+    // the non-ASCII text, as single bytes could as well be accented Latin
+    // letters. An accented letter followed by an ASCII one also looks like a
+    // pair, so some pairs of two non-ASCII bytes are required too.
+    let (_, had_errors) = SHIFT_JIS.decode_without_bom_handling(data);
+    let pairs = count_sjis_pairs(data);
+    if !had_errors && pairs.non_ascii > 0 && pairs.total() > pairs.single_bytes * 2 {
+        return (Encoding::ShiftJis, false);
+    }
+    // The 0x80-0x9f range holds control characters in ISO-8859-1, which are
+    // unlikely to appear in text.
+    if data.iter().any(|byte| (0x80..0xa0).contains(byte)) {
+        (Encoding::Windows1252, false)
+    } else {
+        (Encoding::Latin1, false)
+    }
+}
+
+/// Decodes some text to UTF-8, a leading byte order mark is skipped.
+pub(super) fn decode(data: &[u8], encoding: Encoding) -> Result<String, String> {
+    let data = data.strip_prefix(encoding.bom()).unwrap_or(data);
+    let invalid = || format!("Invalid {} text", encoding.name());
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(data.to_vec()).map_err(|_| invalid()),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let pairs = data.chunks_exact(2);
+            if !pairs.remainder().is_empty() {
+                return Err(invalid());
+            }
+            let units = pairs.map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            });
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|_| invalid())
+        }
+        Encoding::Latin1 => Ok(data.iter().map(|&byte| byte as char).collect()),
+        Encoding::Windows1252 | Encoding::ShiftJis => {
+            let codec = if encoding == Encoding::ShiftJis {
+                SHIFT_JIS
+            } else {
+                WINDOWS_1252
+            };
+            match codec.decode_without_bom_handling_and_without_replacement(data) {
+                Some(text) => Ok(text.into_owned()),
+                None => Err(invalid()),
+            }
+        }
+    }
+}
+
+/// Encodes some UTF-8 text, failing if a character cannot be represented.
+pub(super) fn encode(text: &str, encoding: Encoding, bom: bool) -> Result<Vec<u8>, String> {
+    let mut data = Vec::with_capacity(text.len());
+    if bom {
+        data.extend_from_slice(encoding.bom());
+    }
+    let unmappable = || format!("The text cannot be represented in {}", encoding.name());
+    match encoding {
+        Encoding::Utf8 => data.extend_from_slice(text.as_bytes()),
+        Encoding::Utf16Le => {
+            for unit in text.encode_utf16() {
+                data.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        Encoding::Utf16Be => {
+            for unit in text.encode_utf16() {
+                data.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        Encoding::Latin1 => {
+            for c in text.chars() {
+                data.push(u8::try_from(c).map_err(|_| unmappable())?);
+            }
+        }
+        Encoding::Windows1252 | Encoding::ShiftJis => {
+            let codec = if encoding == Encoding::ShiftJis {
+                SHIFT_JIS
+            } else {
+                WINDOWS_1252
+            };
+            let (encoded, _, had_errors) = codec.encode(text);
+            if had_errors {
+                return Err(unmappable());
+            }
+            data.extend_from_slice(&encoded);
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODINGS: [Encoding; 6] = [
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::Latin1,
+        Encoding::Windows1252,
+        Encoding::ShiftJis,
+    ];
+
+    fn detect_encoding(data: &[u8]) -> Encoding {
+        detect(data).0
+    }
+
+    #[test]
+    fn detect_bom() {
+        assert!(detect(b"\xef\xbb\xbfabc") == (Encoding::Utf8, true));
+        assert!(detect(b"\xff\xfea\0") == (Encoding::Utf16Le, true));
+        assert!(detect(b"\xfe\xff\0a") == (Encoding::Utf16Be, true));
+    }
+
+    #[test]
+    fn detect_without_bom() {
+        assert!(detect_encoding(b"") == Encoding::Utf8);
+        assert!(detect_encoding("Straße schön\n".as_bytes()) == Encoding::Utf8);
+        assert!(detect_encoding(b"h\0e\0l\0l\0o\0\n\0") == Encoding::Utf16Le);
+        assert!(detect_encoding(b"\0h\0e\0l\0l\0o\0\n") == Encoding::Utf16Be);
+        assert!(detect_encoding(b"caf\xe9 \x93quoted\x94\n") == Encoding::Windows1252);
+        assert!(detect_encoding(b"caf\xe9 na\xefve\n") == Encoding::Latin1);
+    }
+
+    #[test]
+    fn detect_latin_letters_as_latin() {
+        // Every accented letter here is also a valid Shift_JIS byte or pair.
+        let text = encode("Straße schön\n", Encoding::Latin1, false).unwrap();
+        assert!(!SHIFT_JIS.decode_without_bom_handling(&text).1);
+        assert!(detect_encoding(&text) == Encoding::Latin1);
+        let text = encode("Àl’été\n", Encoding::Windows1252, false).unwrap();
+        assert!(detect_encoding(&text) == Encoding::Windows1252);
+    }
+
+    #[test]
+    fn detect_shift_jis() {
+        let text = encode("日本語のテキストです。\n", Encoding::ShiftJis, false).unwrap();
+        assert!(detect_encoding(&text) == Encoding::ShiftJis);
+        let text = encode("ｶﾀｶﾅ 漢字とひらがな\n", Encoding::ShiftJis, false).unwrap();
+        assert!(detect_encoding(&text) == Encoding::ShiftJis);
+    }
+
+    #[test]
+    fn round_trip() {
+        let samples = [
+            "",
+            "plain ASCII\r\n",
+            "café ÿ",
+            "“quotes” €",
+            "日本語のテキスト",
+        ];
+        for &encoding in &ENCODINGS {
+            for &bom in &[false, true] {
+                for text in &samples {
+                    let data = match encode(text, encoding, bom) {
+                        Ok(data) => data,
+                        Err(_) => continue,
+                    };
+                    assert!(data.starts_with(if bom { encoding.bom() } else { b"" }));
+                    assert_eq!(decode(&data, encoding).as_deref(), Ok(*text));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encode_unmappable() {
+        assert!(encode("€", Encoding::Latin1, false).is_err());
+        assert!(encode("日本", Encoding::Windows1252, false).is_err());
+        assert!(encode("€", Encoding::Windows1252, false) == Ok(vec![0x80]));
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert!(decode(b"\xff", Encoding::Utf8).is_err());
+        assert!(decode(b"a\0b", Encoding::Utf16Le).is_err());
+        assert!(decode(b"\x00\xd8", Encoding::Utf16Le).is_err());
+        assert!(decode(b"\x82", Encoding::ShiftJis).is_err());
+    }
+}
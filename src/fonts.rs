@@ -0,0 +1,73 @@
+use crate::renderer::FontNames;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+pub(super) struct FontEntry {
+    pub(super) path: PathBuf,
+    pub(super) family: String,
+    pub(super) style: String,
+}
+
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+    ];
+    let home = env::var_os("HOME").map(PathBuf::from);
+    match env::var_os("XDG_DATA_HOME") {
+        Some(data_home) if !data_home.is_empty() => dirs.push(Path::new(&data_home).join("fonts")),
+        _ => {
+            if let Some(home) = &home {
+                dirs.push(home.join(".local/share/fonts"));
+            }
+        }
+    }
+    if let Some(home) = &home {
+        dirs.push(home.join(".fonts"));
+    }
+    dirs
+}
+
+fn is_font_file(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("ttf") || ext.eq_ignore_ascii_case("otf"),
+        None => false,
+    }
+}
+
+fn scan_dir(dir: &Path, fonts: &mut Vec<FontEntry>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Symlinked directories are not followed to avoid scanning loops.
+        match entry.file_type() {
+            Ok(type_) if type_.is_dir() => scan_dir(&path, fonts),
+            Ok(_) if is_font_file(&path) => {
+                if let Some(names) = fs::read(&path).ok().and_then(|data| FontNames::read(&data)) {
+                    fonts.push(FontEntry {
+                        path,
+                        family: names.family,
+                        style: names.style,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lists the fonts installed in the standard font directories, sorted by
+/// family and style.
+pub(super) fn list_fonts() -> Vec<FontEntry> {
+    let mut fonts = Vec::new();
+    for dir in font_dirs() {
+        scan_dir(&dir, &mut fonts);
+    }
+    fonts.sort_by(|a, b| (&a.family, &a.style, &a.path).cmp(&(&b.family, &b.style, &b.path)));
+    fonts
+}
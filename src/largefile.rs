@@ -0,0 +1,370 @@
+use memchr::{memchr_iter, memmem::Finder};
+use std::{
+    fs::File,
+    io,
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// How many bytes are scanned before the lines found in them are published.
+const INDEX_CHUNK: usize = 4 << 20;
+
+/// How many bytes are read at once while searching.
+const SEARCH_CHUNK: usize = 1 << 20;
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+/// Fills as much of `buf` as the file holds from an offset, returns the number
+/// of bytes read. Fewer bytes are read past the end of the file, which may
+/// have been truncated since it was opened.
+fn read_full_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match read_at(file, &mut buf[read..], offset + read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(read)
+}
+
+struct LineIndex {
+    /// The offset at which each line starts, followed by the offset right
+    /// after the newline ending the last line once the whole file is indexed.
+    starts: Mutex<Vec<u64>>,
+    complete: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+/// The result of a search which only reads part of the file at once.
+pub(super) enum Search {
+    /// The line and the column where the text was found.
+    Found(usize, usize),
+    NotFound,
+    /// The search stopped before the end of the file, it goes on from this
+    /// line and column.
+    Stopped(usize, usize),
+}
+
+/// A file too large to be loaded at once. It is read on demand with positioned
+/// reads rather than mapped into memory: reading a mapping past the end of a
+/// file truncated meanwhile raises SIGBUS, while a read just comes up short.
+/// Its lines are indexed by a background thread so that the start of the file
+/// can be shown before the whole of it has been read.
+pub(super) struct LargeFile {
+    file: Arc<File>,
+    len: u64,
+    index: Arc<LineIndex>,
+}
+
+fn build_index(file: &File, len: u64, index: &LineIndex) -> io::Result<()> {
+    let mut buf = vec![0; INDEX_CHUNK];
+    let mut offset = 0;
+    let mut last_byte = None;
+    while offset < len {
+        if index.cancelled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let read = read_full_at(file, &mut buf, offset)?;
+        if read == 0 {
+            break;
+        }
+        let starts = memchr_iter(b'\n', &buf[..read]).map(|i| offset + i as u64 + 1);
+        index.starts.lock().unwrap().extend(starts);
+        last_byte = Some(buf[read - 1]);
+        offset += read as u64;
+    }
+    // The end of the last line, a newline ending the file does not start
+    // another line.
+    if last_byte != Some(b'\n') {
+        index.starts.lock().unwrap().push(offset + 1);
+    }
+    index.complete.store(true, Ordering::Release);
+    Ok(())
+}
+
+impl LargeFile {
+    /// Opens a file and starts indexing its lines.
+    pub(super) fn open(path: &Path) -> io::Result<Self> {
+        let file = Arc::new(File::open(path)?);
+        let len = file.metadata()?.len();
+        let index = Arc::new(LineIndex {
+            starts: Mutex::new(vec![0]),
+            complete: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+        });
+        let (thread_file, thread_index) = (Arc::clone(&file), Arc::clone(&index));
+        thread::Builder::new()
+            .name("line index".to_string())
+            .spawn(move || {
+                // The lines read so far stay available if reading fails.
+                if build_index(&thread_file, len, &thread_index).is_err() {
+                    thread_index.complete.store(true, Ordering::Release);
+                }
+            })?;
+        Ok(Self { file, len, index })
+    }
+
+    /// Returns the size of the file when it was opened.
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(super) fn is_indexed(&self) -> bool {
+        self.index.complete.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of lines indexed so far.
+    pub(super) fn line_count(&self) -> usize {
+        // A line is only known to be whole once the next one starts.
+        self.index.starts.lock().unwrap().len() - 1
+    }
+
+    /// Returns the byte range of a line, without its newline.
+    fn line_range(&self, line: usize) -> Option<Range<u64>> {
+        let starts = self.index.starts.lock().unwrap();
+        Some(*starts.get(line)?..*starts.get(line + 1)? - 1)
+    }
+
+    /// Returns a range of bytes of a line, or `None` if the line is not indexed
+    /// yet. The range is limited to the line, as lines can be too long to be
+    /// read whole, and only the part which is still in the file is returned.
+    pub(super) fn line(&self, line: usize, bytes: Range<usize>) -> Option<Vec<u8>> {
+        let range = self.line_range(line)?;
+        let len = range.end - range.start;
+        let start = len.min(bytes.start as u64);
+        let end = len.min(bytes.end as u64).max(start);
+        let mut text = vec![0; (end - start) as usize];
+        let read = read_full_at(&self.file, &mut text, range.start + start).ok()?;
+        text.truncate(read);
+        Some(text)
+    }
+
+    /// Returns the line and the column of an offset in the indexed lines.
+    fn position(&self, offset: u64) -> (usize, usize) {
+        let starts = self.index.starts.lock().unwrap();
+        let line = starts.partition_point(|&start| start <= offset) - 1;
+        (line, (offset - starts[line]) as usize)
+    }
+
+    /// Finds the first occurrence of `needle` from a line and a column. At
+    /// most `limit` bytes are searched at once so that a search through the
+    /// whole file can be spread over several calls, nor is the search carried
+    /// past the lines indexed so far.
+    pub(super) fn find(
+        &self,
+        needle: &[u8],
+        line: usize,
+        col: usize,
+        no_case: bool,
+        limit: u64,
+    ) -> io::Result<Search> {
+        if needle.is_empty() {
+            return Ok(Search::NotFound);
+        }
+        // The index may complete in between, so it is checked first.
+        let complete = self.is_indexed();
+        let range = match self.line_range(line) {
+            Some(range) => range,
+            None if complete => return Ok(Search::NotFound),
+            None => return Ok(Search::Stopped(line, col)),
+        };
+        let from = (range.start + col as u64).min(range.end + 1);
+        // Matches may only start on an indexed line.
+        let indexed_end = {
+            let starts = self.index.starts.lock().unwrap();
+            starts[starts.len() - 1]
+        };
+        let end = indexed_end.min(from.saturating_add(limit.max(1)));
+
+        let needle = if no_case {
+            needle.to_ascii_lowercase()
+        } else {
+            needle.to_vec()
+        };
+        let finder = Finder::new(&needle);
+        // The chunks overlap so that matches across them are found.
+        let mut buf = vec![0; SEARCH_CHUNK + needle.len() - 1];
+        let mut offset = from;
+        while offset < end {
+            let read = read_full_at(&self.file, &mut buf, offset)?;
+            let haystack = &mut buf[..read];
+            if no_case {
+                haystack.make_ascii_lowercase();
+            }
+            if let Some(i) = finder.find(haystack) {
+                let start = offset + i as u64;
+                if start >= end {
+                    // It is found again when the search goes on.
+                    break;
+                }
+                let (line, col) = self.position(start);
+                return Ok(Search::Found(line, col));
+            }
+            if read < buf.len() {
+                // The file ends here, it may have been truncated.
+                return Ok(Search::NotFound);
+            }
+            offset += SEARCH_CHUNK as u64;
+        }
+        if end == indexed_end && complete {
+            Ok(Search::NotFound)
+        } else {
+            let (line, col) = self.position(end);
+            Ok(Search::Stopped(line, col))
+        }
+    }
+}
+
+impl Drop for LargeFile {
+    fn drop(&mut self) {
+        self.index.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    /// Writes a file in the temporary directory and opens it once indexed.
+    fn open_indexed(name: &str, content: &[u8]) -> (LargeFile, std::path::PathBuf) {
+        let path = env::temp_dir().join(format!("largefile-test-{}-{}", process::id(), name));
+        fs::write(&path, content).unwrap();
+        let file = LargeFile::open(&path).unwrap();
+        while !file.is_indexed() {
+            thread::yield_now();
+        }
+        (file, path)
+    }
+
+    fn lines(file: &LargeFile) -> Vec<Vec<u8>> {
+        (0..file.line_count())
+            .map(|line| file.line(line, 0..usize::MAX).unwrap())
+            .collect()
+    }
+
+    fn find(file: &LargeFile, needle: &str, line: usize, col: usize, no_case: bool) -> Search {
+        file.find(needle.as_bytes(), line, col, no_case, u64::MAX)
+            .unwrap()
+    }
+
+    fn found(search: Search) -> Option<(usize, usize)> {
+        match search {
+            Search::Found(line, col) => Some((line, col)),
+            Search::NotFound => None,
+            Search::Stopped(..) => panic!("the search stopped"),
+        }
+    }
+
+    #[test]
+    fn index_lines() {
+        let (file, path) = open_indexed("lines", b"a\nbc\r\n\nd");
+        assert_eq!(file.len(), 8);
+        assert_eq!(lines(&file), [&b"a"[..], b"bc\r", b"", b"d"]);
+        assert!(file.line(4, 0..usize::MAX).is_none());
+        // Ranges are limited to the line.
+        assert_eq!(file.line(1, 1..2).unwrap(), b"c");
+        assert_eq!(file.line(1, 1..10).unwrap(), b"c\r");
+        assert_eq!(file.line(1, 5..10).unwrap(), b"");
+        fs::remove_file(path).unwrap();
+
+        let (file, path) = open_indexed("newline", b"a\n");
+        assert_eq!(lines(&file), [b"a"]);
+        fs::remove_file(path).unwrap();
+
+        let (file, path) = open_indexed("empty", b"");
+        assert_eq!(lines(&file), [b""]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn index_chunks() {
+        // The lines of 7 bytes span the chunks read by the indexer.
+        let content: Vec<u8> = (0..INDEX_CHUNK / 4)
+            .flat_map(|i| format!("{:06}\n", i).into_bytes())
+            .collect();
+        let (file, path) = open_indexed("chunks", &content);
+        assert_eq!(file.line_count(), INDEX_CHUNK / 4);
+        let boundary = INDEX_CHUNK / 7;
+        for &line in &[0, 1, boundary, boundary + 1, INDEX_CHUNK / 4 - 1] {
+            assert_eq!(
+                file.line(line, 0..usize::MAX).unwrap(),
+                format!("{:06}", line).as_bytes()
+            );
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn find_text() {
+        let (file, path) = open_indexed("find", b"one two\nTwo three\ntwo");
+        assert_eq!(found(find(&file, "two", 0, 0, false)), Some((0, 4)));
+        assert_eq!(found(find(&file, "two", 0, 5, false)), Some((2, 0)));
+        assert_eq!(found(find(&file, "two", 0, 5, true)), Some((1, 0)));
+        assert_eq!(found(find(&file, "TWO", 1, 1, true)), Some((2, 0)));
+        assert_eq!(found(find(&file, "two\nTwo", 0, 0, false)), Some((0, 4)));
+        assert_eq!(found(find(&file, "four", 0, 0, true)), None);
+        assert_eq!(found(find(&file, "two", 2, 1, false)), None);
+        assert_eq!(found(find(&file, "", 0, 0, false)), None);
+        assert_eq!(found(find(&file, "two", 3, 0, false)), None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn find_in_parts() {
+        // The text is split between two chunks read by the search.
+        let mut content = vec![b'.'; SEARCH_CHUNK * 3 - 2];
+        content.extend_from_slice(b"Needle\n");
+        let (file, path) = open_indexed("parts", &content);
+        assert_eq!(
+            found(find(&file, "needle", 0, 0, true)),
+            Some((0, SEARCH_CHUNK * 3 - 2))
+        );
+
+        let mut search = file
+            .find(b"needle", 0, 0, true, SEARCH_CHUNK as u64)
+            .unwrap();
+        let mut calls = 1;
+        while let Search::Stopped(line, col) = search {
+            search = file
+                .find(b"needle", line, col, true, SEARCH_CHUNK as u64)
+                .unwrap();
+            calls += 1;
+        }
+        assert_eq!(found(search), Some((0, SEARCH_CHUNK * 3 - 2)));
+        assert_eq!(calls, 3);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn truncated_file() {
+        let (file, path) = open_indexed("truncated", b"first\nsecond\nthird\n");
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(8)
+            .unwrap();
+        assert_eq!(file.line(0, 0..usize::MAX).unwrap(), b"first");
+        assert_eq!(file.line(1, 0..usize::MAX).unwrap(), b"se");
+        assert_eq!(file.line(2, 0..usize::MAX).unwrap(), b"");
+        assert_eq!(found(find(&file, "third", 0, 0, false)), None);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,19 +1,27 @@
 #![warn(clippy::all)]
 
-use api::api_load_libs;
+use api::{api_load_libs, push_os_str};
 use lua_sys::*;
 use once_cell::sync::Lazy;
 use std::{
     ffi::{CString, OsString},
     fs,
-    os::raw::{c_char, c_long},
+    os::raw::c_long,
     sync::Mutex,
 };
 use window::Window;
 
+pub(self) mod analyze;
 pub(self) mod api;
+pub(self) mod atomic;
+pub(self) mod clipboard;
+pub(self) mod copy;
+pub(self) mod encoding;
+pub(self) mod fonts;
+pub(self) mod largefile;
 pub(self) mod rencache;
 pub(self) mod renderer;
+pub(self) mod trash;
 pub(self) mod window;
 
 macro_rules! c_str {
@@ -23,8 +31,11 @@ macro_rules! c_str {
 }
 pub(self) use c_str;
 
-pub(self) static mut WINDOW: Lazy<Mutex<Window>> =
-    Lazy::new(|| Mutex::new(Window::init().expect("Could not initialize window")));
+pub(self) static mut WINDOW: Lazy<Mutex<Window>> = Lazy::new(|| {
+    // Other arguments are files or directories to open, handled by Lua.
+    let borderless = std::env::args_os().skip(1).any(|arg| arg == "--borderless");
+    Mutex::new(Window::init(borderless).expect("Could not initialize window"))
+});
 
 fn get_exe_filename() -> String {
     let path = format!("/proc/{}/exe", std::process::id());
@@ -35,19 +46,15 @@ fn get_exe_filename() -> String {
 }
 
 #[cfg(windows)]
-pub(self) unsafe fn os_string_from_ptr(filename: *const c_char) -> OsString {
-    use std::os::windows::ffi::OsStringExt;
-    OsString::from_wide(filename)
+pub(self) fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
 }
 
 #[cfg(unix)]
-pub(self) unsafe fn os_string_from_ptr(filename: *const c_char) -> OsString {
-    use std::{
-        ffi::{CStr, OsStr},
-        os::unix::ffi::OsStrExt,
-    };
+pub(self) fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
 
-    OsStr::from_bytes(CStr::from_ptr(filename).to_bytes()).to_owned()
+    OsStr::from_bytes(bytes).to_owned()
 }
 
 fn main() {
@@ -56,9 +63,8 @@ fn main() {
         luaL_openlibs(state);
         api_load_libs(state);
         lua_createtable(state, 0, 0);
-        for (i, arg) in std::env::args().enumerate() {
-            let arg = CString::new(arg).unwrap();
-            lua_pushstring(state, arg.as_ptr() as *const c_char);
+        for (i, arg) in std::env::args_os().enumerate() {
+            push_os_str(state, &arg);
             lua_rawseti(state, -2, i as c_long + 1);
         }
         lua_setglobal(state, c_str!("ARGS"));
@@ -1,5 +1,5 @@
 use crate::{
-    renderer::{RenColor, RenFont, RenRect, Renderer},
+    renderer::{RenColor, RenFont, RenLine, RenRect, Renderer},
     window::Window,
 };
 use hashers::fnv::FNV1aHasher32;
@@ -21,6 +21,12 @@ pub(super) struct Command {
     color: RenColor,
     font: Option<Box<RenFont>>,
     text: Option<String>,
+    line: RenLine,
+    radius: c_int,
+    stroke: c_int,
+    color2: RenColor,
+    vertical: bool,
+    blur: c_int,
 }
 
 impl Default for Command {
@@ -31,6 +37,12 @@ impl Default for Command {
             color: RenColor::default(),
             font: None,
             text: None,
+            line: RenLine::default(),
+            radius: 0,
+            stroke: 0,
+            color2: RenColor::default(),
+            vertical: false,
+            blur: 0,
         }
     }
 }
@@ -46,6 +58,12 @@ impl Hash for Command {
             .map_or(ptr::null(), |font| font as *const _) as usize)
             .hash(state);
         self.text.hash(state);
+        self.line.hash(state);
+        self.radius.hash(state);
+        self.stroke.hash(state);
+        self.color2.hash(state);
+        self.vertical.hash(state);
+        self.blur.hash(state);
     }
 }
 
@@ -56,6 +74,10 @@ enum CommandType {
     SetClip = 1,
     DrawText = 2,
     DrawRect = 3,
+    DrawLine = 4,
+    DrawRoundedRect = 5,
+    DrawGradientRect = 6,
+    DrawShadow = 7,
 }
 
 unsafe fn hash<T>(hash: &mut c_uint, data: &T) {
@@ -313,6 +335,78 @@ impl RenCache {
         }
     }
 
+    pub(super) fn draw_line(&mut self, line: RenLine, width: c_int, color: RenColor) {
+        let rect = line.bounds(width);
+        if !self.screen_rect.has_overlap(rect) {
+            return;
+        }
+        let cmd = self.command_buf.push_command(CommandType::DrawLine);
+        if let Some(cmd) = cmd {
+            cmd.rect = rect;
+            cmd.line = line;
+            cmd.stroke = width;
+            cmd.color = color;
+        }
+    }
+
+    pub(super) fn draw_rounded_rect(
+        &mut self,
+        rect: RenRect,
+        radius: c_int,
+        stroke: c_int,
+        color: RenColor,
+    ) {
+        if !self.screen_rect.has_overlap(rect) {
+            return;
+        }
+        let cmd = self.command_buf.push_command(CommandType::DrawRoundedRect);
+        if let Some(cmd) = cmd {
+            cmd.rect = rect;
+            cmd.radius = radius;
+            cmd.stroke = stroke;
+            cmd.color = color;
+        }
+    }
+
+    pub(super) fn draw_gradient_rect(
+        &mut self,
+        rect: RenRect,
+        from: RenColor,
+        to: RenColor,
+        vertical: bool,
+    ) {
+        if !self.screen_rect.has_overlap(rect) {
+            return;
+        }
+        let cmd = self.command_buf.push_command(CommandType::DrawGradientRect);
+        if let Some(cmd) = cmd {
+            cmd.rect = rect;
+            cmd.color = from;
+            cmd.color2 = to;
+            cmd.vertical = vertical;
+        }
+    }
+
+    pub(super) fn draw_shadow(
+        &mut self,
+        rect: RenRect,
+        radius: c_int,
+        blur: c_int,
+        color: RenColor,
+    ) {
+        let area = rect.expand(blur);
+        if !self.screen_rect.has_overlap(area) {
+            return;
+        }
+        let cmd = self.command_buf.push_command(CommandType::DrawShadow);
+        if let Some(cmd) = cmd {
+            cmd.rect = area;
+            cmd.radius = radius;
+            cmd.blur = blur;
+            cmd.color = color;
+        }
+    }
+
     pub(super) fn draw_text(
         &mut self,
         font: &mut RenFont,
@@ -406,6 +500,32 @@ impl RenCache {
                     CommandType::DrawRect => {
                         self.renderer.draw_rect(cmd.rect, cmd.color, window);
                     }
+                    CommandType::DrawLine => {
+                        self.renderer
+                            .draw_line(cmd.line, cmd.stroke, cmd.color, window);
+                    }
+                    CommandType::DrawRoundedRect => {
+                        self.renderer
+                            .draw_rounded_rect(cmd.rect, cmd.radius, cmd.stroke, cmd.color, window);
+                    }
+                    CommandType::DrawGradientRect => {
+                        self.renderer.draw_gradient_rect(
+                            cmd.rect,
+                            cmd.color,
+                            cmd.color2,
+                            cmd.vertical,
+                            window,
+                        );
+                    }
+                    CommandType::DrawShadow => {
+                        self.renderer.draw_shadow(
+                            cmd.rect.expand(-cmd.blur),
+                            cmd.radius,
+                            cmd.blur,
+                            cmd.color,
+                            window,
+                        );
+                    }
                     CommandType::DrawText => {
                         self.renderer.draw_text(
                             cmd.font.as_deref_mut().unwrap(),
@@ -6,7 +6,7 @@ use std::{
     mem::{self, MaybeUninit},
     os::raw::{c_double, c_float, c_int},
     path::Path,
-    slice,
+    slice, str,
 };
 
 #[derive(Clone, Debug, Hash)]
@@ -48,6 +48,17 @@ impl RenColor {
         }
     }
 
+    fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t + 0.5) as u8;
+        Self {
+            b: mix(self.b, other.b),
+            g: mix(self.g, other.g),
+            r: mix(self.r, other.r),
+            a: mix(self.a, other.a),
+        }
+    }
+
     fn blend_pixel(mut self, src: Self) -> Self {
         let ia = 0xff - src.a as c_int;
         self.r = ((src.r as c_int * src.a as c_int + self.r as c_int * ia) >> 8) as u8;
@@ -75,13 +86,73 @@ struct VerticalMetrics {
     linegap: i32,
 }
 
+struct UnderlineMetrics {
+    position: i32,
+    thickness: i32,
+}
+
 #[derive(Clone, Debug)]
 struct FontInfo {
     fontinfo: stbtt_fontinfo,
 }
 
+fn read_u16(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Checks the parts of a font stb_truetype reads without bounds checks: the
+/// table directory, the fixed fields of the tables it needs and the records
+/// of the `name` table.
+fn check_font_data(data: &[u8]) -> Option<()> {
+    // Collections are not supported, only a single font at offset 0.
+    match data.get(0..4)? {
+        b"\0\x01\0\0" | b"true" | b"typ1" | b"OTTO" | b"1\0\0\0" => {}
+        _ => return None,
+    }
+    let num_tables = read_u16(data, 4)?;
+    let mut name = None;
+    for i in 0..num_tables {
+        let record = 12 + 16 * i;
+        let tag = data.get(record..record + 4)?;
+        let offset = read_u32(data, record + 8)?;
+        let length = read_u32(data, record + 12)?;
+        let table = data.get(offset..offset.checked_add(length)?)?;
+        let min_length = match tag {
+            b"head" => 54,
+            b"hhea" => 36,
+            b"maxp" => 6,
+            b"cmap" => 4,
+            _ => 0,
+        };
+        if table.len() < min_length {
+            return None;
+        }
+        if tag == b"name" {
+            name = Some(table);
+        }
+    }
+    if let Some(name) = name {
+        let count = read_u16(name, 2)?;
+        let strings = read_u16(name, 4)?;
+        for i in 0..count {
+            let record = 6 + 12 * i;
+            let length = read_u16(name, record + 8)?;
+            let offset = strings + read_u16(name, record + 10)?;
+            name.get(offset..offset + length)?;
+        }
+    }
+    Some(())
+}
+
 impl FontInfo {
     fn init(data: &[u8]) -> Option<Self> {
+        check_font_data(data)?;
         let mut fontinfo: MaybeUninit<stbtt_fontinfo> = MaybeUninit::uninit();
         // SAFETY: fontinfo is garanteed to point to valid (uninitialized) memory.
         let ok = unsafe { stbtt_InitFont(fontinfo.as_mut_ptr(), data.as_ptr(), 0) };
@@ -121,6 +192,90 @@ impl FontInfo {
 
         VerticalMetrics { ascent, descent, linegap }
     }
+
+    fn underline_metrics(&mut self) -> Option<UnderlineMetrics> {
+        // SAFETY: fontinfo is garanteed to be valid.
+        unsafe {
+            let data = self.fontinfo.data;
+            let post = stbtt__find_table(data, self.fontinfo.fontstart as u32, "post");
+            if post == 0 {
+                return None;
+            }
+            Some(UnderlineMetrics {
+                position: ttSHORT(data.offset(post as isize + 8)) as i32,
+                thickness: ttSHORT(data.offset(post as isize + 10)) as i32,
+            })
+        }
+    }
+
+    fn has_glyph(&mut self, codepoint: c_int) -> bool {
+        // SAFETY: fontinfo is garanteed to be valid.
+        unsafe { stbtt_FindGlyphIndex(&mut self.fontinfo, codepoint) != 0 }
+    }
+
+    fn name(&mut self, name_id: c_int) -> Option<String> {
+        // Unicode names from the Windows and Unicode platforms are preferred,
+        // the Macintosh Roman name is only used as a fallback.
+        const PLATFORMS: [(c_int, c_int, c_int); 5] = [
+            (3, 1, 0x409),
+            (3, 10, 0x409),
+            (0, 3, 0),
+            (0, 4, 0),
+            (1, 0, 0),
+        ];
+
+        for &(platform, encoding, language) in &PLATFORMS {
+            let mut length = 0;
+            // SAFETY: fontinfo is garanteed to be valid.
+            let name = unsafe {
+                stbtt_GetFontNameString(
+                    &mut self.fontinfo,
+                    &mut length,
+                    platform,
+                    encoding,
+                    language,
+                    name_id,
+                )
+            };
+            if name.is_null() {
+                continue;
+            }
+            // SAFETY: The returned pointer points into the font data and is
+            //         valid for `length` bytes.
+            let bytes = unsafe { slice::from_raw_parts(name as *const u8, length as usize) };
+            let name = if platform == 1 {
+                bytes.iter().map(|&b| b as char).collect()
+            } else {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            };
+            return Some(name.replace('\0', ""));
+        }
+        None
+    }
+}
+
+/// Family and style names of a font, as stored in its `name` table.
+pub(super) struct FontNames {
+    pub(super) family: String,
+    pub(super) style: String,
+}
+
+impl FontNames {
+    pub(super) fn read(data: &[u8]) -> Option<Self> {
+        let mut stbfont = FontInfo::init(data)?;
+        // Typographic names (16, 17) are not limited to the four classic
+        // styles, so they are preferred over the legacy ones (1, 2).
+        let family = stbfont.name(16).or_else(|| stbfont.name(1))?;
+        let style = stbfont
+            .name(17)
+            .or_else(|| stbfont.name(2))
+            .unwrap_or_else(|| "Regular".to_owned());
+        Some(Self { family, style })
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -135,30 +290,28 @@ pub(super) struct RenFont {
 
 impl RenFont {
     pub(super) fn load<P: AsRef<Path>>(filename: P, size: c_float) -> Option<Box<Self>> {
-         {
-            match fs::read(filename) {
-                Err(_) => Option::None,
-                Ok(data) => {
-                    let data = data.into_boxed_slice();
-                    let mut stbfont = FontInfo::init(&data)?;
-                    let metrics = stbfont.vertical_metrics();
-                    let scale = stbfont.scale_for_mapping_em_to_pixels(size);
-                    let height = (((metrics.ascent - metrics.descent + metrics.linegap) as c_float * scale) as c_double
-                        + 0.5f64) as c_int;
-                    let mut font = Box::new(Self {
-                        data,
-                        stbfont,
-                        sets: [(); 256].map(|_| Option::None),
-                        size,
-                        height,
-                    });
-                    let g = &mut font.get_glyphset_mut('\n' as i32).glyphs;
-                    g['\t' as usize].x1 = g['\t' as usize].x0;
-                    g['\n' as usize].x1 = g['\n' as usize].x0;
-                    Some(font)
-                }
-            }
-        }
+        let data = fs::read(filename).ok()?;
+        Self::load_memory(data.into_boxed_slice(), size)
+    }
+
+    pub(super) fn load_memory(data: Box<[u8]>, size: c_float) -> Option<Box<Self>> {
+        let mut stbfont = FontInfo::init(&data)?;
+        let metrics = stbfont.vertical_metrics();
+        let scale = stbfont.scale_for_mapping_em_to_pixels(size);
+        let height = (((metrics.ascent - metrics.descent + metrics.linegap) as c_float * scale)
+            as c_double
+            + 0.5f64) as c_int;
+        let mut font = Box::new(Self {
+            data,
+            stbfont,
+            sets: [(); 256].map(|_| Option::None),
+            size,
+            height,
+        });
+        let g = &mut font.get_glyphset_mut('\n' as i32).glyphs;
+        g['\t' as usize].x1 = g['\t' as usize].x0;
+        g['\n' as usize].x1 = g['\n' as usize].x0;
+        Some(font)
     }
 
     fn load_glyphset(&mut self, idx: c_int) -> Box<GlyphSet> {
@@ -244,6 +397,125 @@ impl RenFont {
     pub(super) fn height(&self) -> c_int {
         self.height
     }
+
+    fn scaled(&mut self, value: c_int) -> c_int {
+        let scale = self.stbfont.scale_for_mapping_em_to_pixels(self.size);
+        ((value as c_float * scale) as c_double + 0.5f64).floor() as c_int
+    }
+
+    pub(super) fn ascent(&mut self) -> c_int {
+        let ascent = self.stbfont.vertical_metrics().ascent;
+        self.scaled(ascent)
+    }
+
+    /// Distance from the baseline to the bottom of the glyphs, as a positive
+    /// number of pixels.
+    pub(super) fn descent(&mut self) -> c_int {
+        let descent = self.stbfont.vertical_metrics().descent;
+        self.scaled(-descent)
+    }
+
+    pub(super) fn line_gap(&mut self) -> c_int {
+        let linegap = self.stbfont.vertical_metrics().linegap;
+        self.scaled(linegap)
+    }
+
+    /// Returns the offset of the underline from the top of the line and its
+    /// thickness. Fonts without a `post` table get an underline just below
+    /// the baseline.
+    pub(super) fn underline(&mut self) -> (c_int, c_int) {
+        let ascent = self.ascent();
+        match self.stbfont.underline_metrics() {
+            Some(metrics) => {
+                let position = ascent - self.scaled(metrics.position);
+                (position, self.scaled(metrics.thickness).max(1))
+            }
+            None => {
+                let thickness = (self.height / 14).max(1);
+                (ascent + thickness, thickness)
+            }
+        }
+    }
+
+    pub(super) fn advance(&mut self, codepoint: char) -> c_float {
+        let set = self.get_glyphset_mut(codepoint as c_int);
+        set.glyphs[(codepoint as u32 & 0xff) as usize].xadvance
+    }
+
+    pub(super) fn has_glyph(&mut self, codepoint: char) -> bool {
+        self.stbfont.has_glyph(codepoint as c_int)
+    }
+
+    /// Returns the 1-based byte column of the character closest to the `x`
+    /// offset, or the length of the text if `x` is past its end.
+    pub(super) fn char_index_at(&mut self, text: &[u8], x: c_double) -> usize {
+        let mut xoffset = 0;
+        let mut last_i = 1;
+        for (i, codepoint) in LossyCharIndices::new(text) {
+            let w = self.advance(codepoint);
+            if xoffset as c_double >= x {
+                return if xoffset as c_double - x > (w / 2.0) as c_double {
+                    last_i
+                } else {
+                    i + 1
+                };
+            }
+            xoffset = (xoffset as c_float + w) as c_int;
+            last_i = i + 1;
+        }
+        text.len()
+    }
+
+    /// Returns the width of the text preceding the 1-based byte column `col`.
+    pub(super) fn x_offset(&mut self, text: &[u8], col: usize) -> c_int {
+        let end = col.saturating_sub(1).min(text.len());
+        let mut x = 0;
+        for (_, codepoint) in LossyCharIndices::new(&text[..end]) {
+            x = (x as c_float + self.advance(codepoint)) as c_int;
+        }
+        x
+    }
+}
+
+/// Iterates over the characters of possibly invalid UTF-8 text along with
+/// their byte index. Each invalid sequence yields a replacement character.
+struct LossyCharIndices<'a> {
+    text: &'a [u8],
+    index: usize,
+}
+
+impl<'a> LossyCharIndices<'a> {
+    fn new(text: &'a [u8]) -> Self {
+        Self { text, index: 0 }
+    }
+}
+
+impl<'a> Iterator for LossyCharIndices<'a> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.index;
+        let rest = &self.text[start..];
+        if rest.is_empty() {
+            return None;
+        }
+        // A character is at most 4 bytes long, there is no need to validate
+        // the rest of the text.
+        let head = &rest[..rest.len().min(4)];
+        let valid = match str::from_utf8(head) {
+            Ok(valid) => valid,
+            Err(error) if error.valid_up_to() > 0 => {
+                str::from_utf8(&head[..error.valid_up_to()]).unwrap()
+            }
+            Err(error) => {
+                self.index += error.error_len().unwrap_or(head.len());
+                return Some((start, char::REPLACEMENT_CHARACTER));
+            }
+        };
+        let codepoint = valid.chars().next().unwrap();
+        self.index += codepoint.len_utf8();
+        Some((start, codepoint))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -292,6 +564,15 @@ impl RenRect {
         }
     }
 
+    pub(super) fn expand(self, n: c_int) -> Self {
+        Self {
+            x: self.x - n,
+            y: self.y - n,
+            width: self.width + n * 2,
+            height: self.height + n * 2,
+        }
+    }
+
     pub(super) fn union(self, rhs: Self) -> Self {
         let x1 = self.x.min(rhs.x);
         let y1 = self.y.min(rhs.y);
@@ -306,6 +587,59 @@ impl RenRect {
     }
 }
 
+#[derive(Copy, Clone, Debug, Hash)]
+#[repr(C)]
+pub(super) struct RenLine {
+    pub(super) x1: c_int,
+    pub(super) y1: c_int,
+    pub(super) x2: c_int,
+    pub(super) y2: c_int,
+}
+
+impl RenLine {
+    pub(super) const fn default() -> Self {
+        Self {
+            x1: 0,
+            y1: 0,
+            x2: 0,
+            y2: 0,
+        }
+    }
+
+    /// Returns the rectangle covered by the line when drawn `width` pixels
+    /// wide, with a margin for antialiasing.
+    pub(super) fn bounds(self, width: c_int) -> RenRect {
+        let margin = width / 2 + 1;
+        let x = self.x1.min(self.x2) - margin;
+        let y = self.y1.min(self.y2) - margin;
+        RenRect {
+            x,
+            y,
+            width: self.x1.max(self.x2) + margin - x,
+            height: self.y1.max(self.y2) + margin - y,
+        }
+    }
+}
+
+/// Distance from `(px, py)` to a rectangle of half size `(hw, hh)` centered
+/// on the origin and whose corners are rounded with `radius`. The distance is
+/// negative inside the rectangle.
+fn rounded_rect_distance(px: f32, py: f32, hw: f32, hh: f32, radius: f32) -> f32 {
+    let qx = px.abs() - (hw - radius);
+    let qy = py.abs() - (hh - radius);
+    let outside = qx.max(0.0).hypot(qy.max(0.0));
+    let inside = qx.max(qy).min(0.0);
+    outside + inside - radius
+}
+
+/// Approximation of the error function, with a maximum error of 5e-4.
+fn erf(x: f32) -> f32 {
+    let t = x.abs();
+    let d = 1.0 + t * (0.278_393 + t * (0.230_389 + t * (0.000_972 + t * 0.078_108)));
+    let y = 1.0 - 1.0 / (d * d * d * d);
+    y.copysign(x)
+}
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 struct Clip {
@@ -418,6 +752,157 @@ impl Renderer {
         });
     }
 
+    /// Blends the color returned by `shader` into each pixel of `rect` that is
+    /// inside the clip rectangle. `shader` is called with the center of the
+    /// pixel.
+    fn fill_shaded<F>(&mut self, rect: RenRect, window: &Window, shader: F)
+    where
+        F: Fn(f32, f32) -> RenColor,
+    {
+        let x1 = rect.x.max(self.clip.left);
+        let y1 = rect.y.max(self.clip.top);
+        let x2 = (rect.x + rect.width).min(self.clip.right);
+        let y2 = (rect.y + rect.height).min(self.clip.bottom);
+        if x1 >= x2 || y1 >= y2 {
+            return;
+        }
+        let mut surf = window.surface().unwrap();
+        let width = surf.width();
+        let height = surf.height();
+        surf.with_lock_mut(|d| {
+            // SAFETY: The pixels format was configured to have the same layout
+            //         as RenColor when creating the window.
+            let d = unsafe {
+                slice::from_raw_parts_mut(
+                    d.as_mut_ptr() as *mut RenColor,
+                    (width * height) as usize,
+                )
+            };
+            for y in y1..y2 {
+                for x in x1..x2 {
+                    let color = shader(x as f32 + 0.5, y as f32 + 0.5);
+                    if color.a == 0 {
+                        continue;
+                    }
+                    let pixel = &mut d[(x + y * width as c_int) as usize];
+                    *pixel = pixel.blend_pixel(color);
+                }
+            }
+        });
+    }
+
+    /// Blends `color` into the pixels of `rect` that are inside the clip
+    /// rectangle. `coverage` is called with the center of each pixel and
+    /// returns how much of the pixel is covered by the shape, from 0 to 1.
+    fn fill_coverage<F>(&mut self, rect: RenRect, color: RenColor, window: &Window, coverage: F)
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        if color.a == 0 {
+            return;
+        }
+        self.fill_shaded(rect, window, |px, py| RenColor {
+            a: (color.a as f32 * coverage(px, py).clamp(0.0, 1.0)) as u8,
+            ..color
+        });
+    }
+
+    pub(super) fn draw_line(
+        &mut self,
+        line: RenLine,
+        width: c_int,
+        color: RenColor,
+        window: &Window,
+    ) {
+        let (ax, ay) = (line.x1 as f32, line.y1 as f32);
+        let (dx, dy) = ((line.x2 - line.x1) as f32, (line.y2 - line.y1) as f32);
+        let length2 = dx * dx + dy * dy;
+        let half_width = width.max(1) as f32 / 2.0;
+        self.fill_coverage(line.bounds(width), color, window, |px, py| {
+            let t = if length2 > 0.0 {
+                (((px - ax) * dx + (py - ay) * dy) / length2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let distance = (px - ax - t * dx).hypot(py - ay - t * dy);
+            half_width + 0.5 - distance
+        });
+    }
+
+    /// Draws a rectangle with rounded corners. A `stroke` of 0 fills the
+    /// rectangle, otherwise only a border of `stroke` pixels is drawn.
+    pub(super) fn draw_rounded_rect(
+        &mut self,
+        rect: RenRect,
+        radius: c_int,
+        stroke: c_int,
+        color: RenColor,
+        window: &Window,
+    ) {
+        let hw = rect.width as f32 / 2.0;
+        let hh = rect.height as f32 / 2.0;
+        let cx = rect.x as f32 + hw;
+        let cy = rect.y as f32 + hh;
+        let radius = (radius as f32).max(0.0).min(hw.min(hh));
+        let stroke = stroke as f32;
+        self.fill_coverage(rect, color, window, |px, py| {
+            let distance = rounded_rect_distance(px - cx, py - cy, hw, hh, radius);
+            let outer = (0.5 - distance).clamp(0.0, 1.0);
+            if stroke > 0.0 {
+                let inner = (0.5 - distance - stroke).clamp(0.0, 1.0);
+                outer - inner
+            } else {
+                outer
+            }
+        });
+    }
+
+    pub(super) fn draw_gradient_rect(
+        &mut self,
+        rect: RenRect,
+        from: RenColor,
+        to: RenColor,
+        vertical: bool,
+        window: &Window,
+    ) {
+        if from.a == 0 && to.a == 0 {
+            return;
+        }
+        let (start, length) = if vertical {
+            (rect.y as f32, rect.height.max(1) as f32)
+        } else {
+            (rect.x as f32, rect.width.max(1) as f32)
+        };
+        self.fill_shaded(rect, window, |px, py| {
+            let t = ((if vertical { py } else { px }) - start) / length;
+            from.lerp(to, t)
+        });
+    }
+
+    /// Draws a blurred shadow for the box `rect` with rounded corners of
+    /// `radius`, spreading `blur` pixels around the box.
+    pub(super) fn draw_shadow(
+        &mut self,
+        rect: RenRect,
+        radius: c_int,
+        blur: c_int,
+        color: RenColor,
+        window: &Window,
+    ) {
+        let hw = rect.width as f32 / 2.0;
+        let hh = rect.height as f32 / 2.0;
+        let cx = rect.x as f32 + hw;
+        let cy = rect.y as f32 + hh;
+        let radius = (radius as f32).max(0.0).min(hw.min(hh));
+        // The shadow is approximated by a gaussian of the distance to the box
+        // edge, which is cheap and close enough for small blur radii.
+        let sigma = (blur as f32 / 2.0).max(0.5);
+        self.fill_coverage(rect.expand(blur), color, window, |px, py| {
+            let distance = rounded_rect_distance(px - cx, py - cy, hw, hh, radius);
+            0.5 - 0.5 * erf(distance / (sigma * std::f32::consts::SQRT_2))
+        });
+    }
+
     pub(super) fn draw_image(
         &mut self,
         image: &RenImage,
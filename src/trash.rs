@@ -0,0 +1,395 @@
+use crate::copy::{copy_path, remove_path};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    mem,
+    path::{Component, Path, PathBuf},
+    ptr,
+};
+
+/// A file in the home trash, as described by the freedesktop.org Trash
+/// specification.
+pub(super) struct TrashEntry {
+    /// The name of the file inside of the trash.
+    pub(super) name: OsString,
+    pub(super) path: PathBuf,
+    pub(super) deletion_date: String,
+}
+
+fn trash_dir() -> io::Result<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(data_home) if !data_home.is_empty() => Ok(Path::new(&data_home).join("Trash")),
+        _ => match env::var_os("HOME") {
+            Some(home) => Ok(Path::new(&home).join(".local/share/Trash")),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not find the home directory",
+            )),
+        },
+    }
+}
+
+fn info_name(name: &OsStr) -> OsString {
+    let mut info_name = name.to_os_string();
+    info_name.push(".trashinfo");
+    info_name
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Percent-encodes a path the way URIs are, keeping the separators.
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path_to_bytes(path) {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+fn decode_path(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = encoded.get(i + 1..i + 3);
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    path_from_bytes(decoded)
+}
+
+#[cfg(unix)]
+unsafe fn localtime(time: &libc::time_t, tm: &mut libc::tm) {
+    libc::localtime_r(time, tm);
+}
+
+#[cfg(windows)]
+unsafe fn localtime(time: &libc::time_t, tm: &mut libc::tm) {
+    libc::localtime_s(tm, time);
+}
+
+/// Returns the current local time in the `YYYY-MM-DDThh:mm:ss` format.
+fn deletion_date() -> String {
+    let tm = unsafe {
+        let mut tm = mem::zeroed::<libc::tm>();
+        localtime(&libc::time(ptr::null_mut()), &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+#[cfg(unix)]
+fn is_cross_device(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(libc::EXDEV)
+}
+
+#[cfg(windows)]
+fn is_cross_device(error: &io::Error) -> bool {
+    // ERROR_NOT_SAME_DEVICE
+    error.raw_os_error() == Some(17)
+}
+
+/// Renames a file or a directory, or copies then removes it when it is moved
+/// to another file system, as the home trash may be on another one.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(error) if is_cross_device(&error) => {
+            copy_path(from, to)?;
+            remove_path(from)
+        }
+        result => result,
+    }
+}
+
+/// Finds a name which is neither used in the files nor in the info directory
+/// of the trash, and reserves it by creating its info file.
+fn reserve_name(trash: &Path, path: &Path) -> io::Result<(OsString, File)> {
+    let name = path.file_name().unwrap();
+    for n in 1.. {
+        let candidate = if n == 1 {
+            name.to_os_string()
+        } else {
+            let mut candidate = path.file_stem().unwrap_or(name).to_os_string();
+            candidate.push(format!(" {}", n));
+            if let Some(extension) = path.extension() {
+                candidate.push(".");
+                candidate.push(extension);
+            }
+            candidate
+        };
+        if fs::symlink_metadata(trash.join("files").join(&candidate)).is_ok() {
+            continue;
+        }
+        let info = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(trash.join("info").join(info_name(&candidate)));
+        match info {
+            Ok(info) => return Ok((candidate, info)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    unreachable!()
+}
+
+/// Moves a file or a directory to the home trash, returns its name in the
+/// trash.
+pub(super) fn trash(path: &Path) -> io::Result<OsString> {
+    trash_in(&trash_dir()?, path)
+}
+
+fn trash_in(trash: &Path, path: &Path) -> io::Result<OsString> {
+    fs::symlink_metadata(path)?;
+    // Only the parent is canonicalized so that symlinks themselves get
+    // trashed.
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Cannot trash this path"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let path = fs::canonicalize(parent)?.join(name);
+
+    fs::create_dir_all(trash.join("files"))?;
+    fs::create_dir_all(trash.join("info"))?;
+    let (trash_name, mut info) = reserve_name(trash, &path)?;
+    let info_path = trash.join("info").join(info_name(&trash_name));
+    let result = write!(
+        info,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(&path),
+        deletion_date()
+    )
+    .and_then(|_| move_path(&path, &trash.join("files").join(&trash_name)));
+    if let Err(error) = result {
+        let _ = fs::remove_file(info_path);
+        return Err(error);
+    }
+    Ok(trash_name)
+}
+
+/// Reads the original path and the deletion date from an info file.
+fn read_info(info: &Path) -> io::Result<(PathBuf, String)> {
+    let content = fs::read_to_string(info)?;
+    let mut path = None;
+    let mut deletion_date = String::new();
+    for line in content.lines() {
+        if let Some(value) = line.strip_prefix("Path=") {
+            path = Some(decode_path(value));
+        } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+            deletion_date = value.to_string();
+        }
+    }
+    match path {
+        Some(path) => Ok((path, deletion_date)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid trash info file",
+        )),
+    }
+}
+
+/// Lists the files in the home trash, oldest first.
+pub(super) fn list_trash() -> io::Result<Vec<TrashEntry>> {
+    list_trash_in(&trash_dir()?)
+}
+
+fn list_trash_in(trash: &Path) -> io::Result<Vec<TrashEntry>> {
+    let entries = match fs::read_dir(trash.join("info")) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let info = entry.path();
+        if info.extension() != Some(OsStr::new("trashinfo")) {
+            continue;
+        }
+        if let (Some(name), Ok((path, deletion_date))) = (info.file_stem(), read_info(&info)) {
+            files.push(TrashEntry {
+                name: name.to_os_string(),
+                path,
+                deletion_date,
+            });
+        }
+    }
+    files.sort_by(|a, b| (&a.deletion_date, &a.name).cmp(&(&b.deletion_date, &b.name)));
+    Ok(files)
+}
+
+/// Moves a file from the home trash back to where it was, returns its
+/// original path.
+pub(super) fn restore(name: &OsStr) -> io::Result<PathBuf> {
+    restore_from(&trash_dir()?, name)
+}
+
+fn restore_from(trash: &Path, name: &OsStr) -> io::Result<PathBuf> {
+    // The name must not lead out of the trash.
+    let mut components = Path::new(name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Invalid trash file name",
+        ));
+    }
+    let info = trash.join("info").join(info_name(name));
+    let (path, _) = read_info(&info)?;
+    if fs::symlink_metadata(&path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_path(&trash.join("files").join(name), &path)?;
+    fs::remove_file(info)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a test in a new temporary directory, removed afterwards. It is
+    /// passed the directory and the trash inside of it.
+    fn with_trash(name: &str, test: impl FnOnce(&Path, &Path)) {
+        let dir = env::temp_dir().join(format!("trash-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = fs::canonicalize(&dir).unwrap();
+        test(&dir, &dir.join("data/Trash"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn path_encoding() {
+        let path = Path::new("/tmp/a b/ü%.txt");
+        assert_eq!(encode_path(path), "/tmp/a%20b/%C3%BC%25.txt");
+        assert_eq!(decode_path(&encode_path(path)), path);
+        assert_eq!(decode_path("/bad%2"), Path::new("/bad%2"));
+    }
+
+    #[test]
+    fn trash_and_restore() {
+        with_trash("restore", |dir, trash| {
+            let file = dir.join("file.txt");
+            fs::write(&file, "content").unwrap();
+            let name = trash_in(trash, &file).unwrap();
+            assert_eq!(name, "file.txt");
+            assert!(!file.exists());
+
+            let entries = list_trash_in(trash).unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].name, "file.txt");
+            assert_eq!(entries[0].path, file);
+
+            assert_eq!(restore_from(trash, &name).unwrap(), file);
+            assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+            assert!(list_trash_in(trash).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn trash_directory() {
+        with_trash("directory", |dir, trash| {
+            let subdir = dir.join("dir");
+            fs::create_dir(&subdir).unwrap();
+            fs::write(subdir.join("file"), "content").unwrap();
+            let name = trash_in(trash, &subdir).unwrap();
+            let trashed = trash.join("files").join(&name);
+            assert_eq!(fs::read_to_string(trashed.join("file")).unwrap(), "content");
+            restore_from(trash, &name).unwrap();
+            assert!(subdir.join("file").exists());
+        });
+    }
+
+    #[test]
+    fn trash_same_name() {
+        with_trash("same-name", |dir, trash| {
+            let file = dir.join("file.txt");
+            fs::write(&file, "first").unwrap();
+            assert_eq!(trash_in(trash, &file).unwrap(), "file.txt");
+            fs::write(&file, "second").unwrap();
+            assert_eq!(trash_in(trash, &file).unwrap(), "file 2.txt");
+            assert_eq!(list_trash_in(trash).unwrap().len(), 2);
+
+            fs::write(&file, "third").unwrap();
+            assert_eq!(
+                restore_from(trash, OsStr::new("file 2.txt"))
+                    .unwrap_err()
+                    .kind(),
+                io::ErrorKind::AlreadyExists
+            );
+            fs::remove_file(&file).unwrap();
+            restore_from(trash, OsStr::new("file 2.txt")).unwrap();
+            assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+        });
+    }
+
+    #[test]
+    fn restore_outside_of_trash() {
+        with_trash("outside", |_, trash| {
+            let info = trash.join("info");
+            fs::create_dir_all(&info).unwrap();
+            fs::write(trash.join("x.trashinfo"), "[Trash Info]\nPath=/tmp/x\n").unwrap();
+            for name in &["../x", "files/../../x", "/tmp/x", "..", ""] {
+                assert_eq!(
+                    restore_from(trash, OsStr::new(name)).unwrap_err().kind(),
+                    io::ErrorKind::InvalidInput
+                );
+            }
+        });
+    }
+}
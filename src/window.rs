@@ -1,17 +1,35 @@
+use once_cell::sync::Lazy;
 use sdl2::{
-    clipboard::ClipboardUtil,
     event::{Event as SdlEvent, EventType, WindowEvent},
-    mouse::{MouseButton, MouseUtil},
-    sys::SDL_WindowFlags,
-    video::{FullscreenType, Window as SdlWindow, WindowSurfaceRef},
-    EventPump, EventSubsystem, Sdl,
+    mouse::{Cursor, MouseButton, MouseWheelDirection, SystemCursor},
+    rect::Rect,
+    surface::{Surface, SurfaceRef},
+    sys::{
+        SDL_AddEventWatch, SDL_Event, SDL_EventType, SDL_GetGlobalMouseState, SDL_GetWindowSurface,
+        SDL_HitTestResult, SDL_Point, SDL_Rect, SDL_SetWindowHitTest, SDL_UpdateWindowSurfaceRects,
+        SDL_Window, SDL_WindowFlags, SDL_bool,
+    },
+    video::{FullscreenType, Window as SdlWindow, WindowPos},
+    EventPump, Sdl,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    mem,
+    ops::{Deref, DerefMut},
+    os::raw::{c_char, c_int, c_uint, c_void},
+    path::Path,
+    ptr,
+    rc::Rc,
+    sync::Mutex,
 };
-use std::os::raw::{c_int, c_uint};
 
 pub(super) enum WindowMode {
     Normal = 0,
     Maximized = 1,
     Fullscreen = 2,
+    Minimized = 3,
+    Borderless = 4,
 }
 
 impl WindowMode {
@@ -20,24 +38,196 @@ impl WindowMode {
             0 => Self::Normal,
             1 => Self::Maximized,
             2 => Self::Fullscreen,
+            3 => Self::Minimized,
+            4 => Self::Borderless,
             _ => panic!("Invalid value for WindowMode: {}", mode),
         }
     }
+
+    pub(super) fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Maximized => "maximized",
+            Self::Fullscreen => "fullscreen",
+            Self::Minimized => "minimized",
+            Self::Borderless => "borderless",
+        }
+    }
+}
+
+/// Looks up a function of the SDL library in use, for the functions which are
+/// newer than the bindings and may be missing from an older library.
+#[cfg(unix)]
+pub(super) unsafe fn sdl_function(name: &[u8]) -> Option<*mut c_void> {
+    let function = libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const c_char);
+    if function.is_null() {
+        None
+    } else {
+        Some(function)
+    }
+}
+
+#[cfg(windows)]
+pub(super) unsafe fn sdl_function(name: &[u8]) -> Option<*mut c_void> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleA(name: *const c_char) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+
+    let module = GetModuleHandleA(b"SDL2.dll\0".as_ptr() as *const c_char);
+    if module.is_null() {
+        return None;
+    }
+    let function = GetProcAddress(module, name.as_ptr() as *const c_char);
+    if function.is_null() {
+        None
+    } else {
+        Some(function)
+    }
+}
+
+type SetWindowAlwaysOnTop = unsafe extern "C" fn(*mut SDL_Window, SDL_bool);
+type FlashWindow = unsafe extern "C" fn(*mut SDL_Window, c_int) -> c_int;
+
+/// The window functions added by SDL 2.0.16, which the bindings predate.
+struct WindowFunctions {
+    set_always_on_top: Option<SetWindowAlwaysOnTop>,
+    flash: Option<FlashWindow>,
+}
+
+static WINDOW_FUNCTIONS: Lazy<WindowFunctions> = Lazy::new(|| unsafe {
+    WindowFunctions {
+        set_always_on_top: sdl_function(b"SDL_SetWindowAlwaysOnTop\0")
+            .map(|function| mem::transmute::<*mut c_void, SetWindowAlwaysOnTop>(function)),
+        flash: sdl_function(b"SDL_FlashWindow\0")
+            .map(|function| mem::transmute::<*mut c_void, FlashWindow>(function)),
+    }
+});
+
+pub(super) enum FlashMode {
+    Cancel = 0,
+    Briefly = 1,
+    UntilFocused = 2,
+}
+
+impl FlashMode {
+    pub(super) fn from_raw(mode: i32) -> Self {
+        match mode {
+            0 => Self::Cancel,
+            1 => Self::Briefly,
+            2 => Self::UntilFocused,
+            _ => panic!("Invalid value for FlashMode: {}", mode),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(super) enum HitTest {
+    Normal = 0,
+    Draggable = 1,
+    ResizeTopLeft = 2,
+    ResizeTop = 3,
+    ResizeTopRight = 4,
+    ResizeRight = 5,
+    ResizeBottomRight = 6,
+    ResizeBottom = 7,
+    ResizeBottomLeft = 8,
+    ResizeLeft = 9,
+}
+
+impl HitTest {
+    pub(super) fn from_raw(hit: i32) -> Self {
+        match hit {
+            0 => Self::Normal,
+            1 => Self::Draggable,
+            2 => Self::ResizeTopLeft,
+            3 => Self::ResizeTop,
+            4 => Self::ResizeTopRight,
+            5 => Self::ResizeRight,
+            6 => Self::ResizeBottomRight,
+            7 => Self::ResizeBottom,
+            8 => Self::ResizeBottomLeft,
+            9 => Self::ResizeLeft,
+            _ => panic!("Invalid value for HitTest: {}", hit),
+        }
+    }
+
+    fn to_sdl(self) -> SDL_HitTestResult {
+        match self {
+            Self::Normal => SDL_HitTestResult::SDL_HITTEST_NORMAL,
+            Self::Draggable => SDL_HitTestResult::SDL_HITTEST_DRAGGABLE,
+            Self::ResizeTopLeft => SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPLEFT,
+            Self::ResizeTop => SDL_HitTestResult::SDL_HITTEST_RESIZE_TOP,
+            Self::ResizeTopRight => SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPRIGHT,
+            Self::ResizeRight => SDL_HitTestResult::SDL_HITTEST_RESIZE_RIGHT,
+            Self::ResizeBottomRight => SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOMRIGHT,
+            Self::ResizeBottom => SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOM,
+            Self::ResizeBottomLeft => SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOMLEFT,
+            Self::ResizeLeft => SDL_HitTestResult::SDL_HITTEST_RESIZE_LEFT,
+        }
+    }
+}
+
+/// An area of the window which behaves like a part of the decorations, such
+/// as a title bar or a resize border, when the window is borderless.
+pub(super) struct HitRegion {
+    pub(super) x: i32,
+    pub(super) y: i32,
+    pub(super) width: i32,
+    pub(super) height: i32,
+    pub(super) hit: HitTest,
+}
+
+impl HitRegion {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Called by SDL with the regions of the window, the first region containing
+/// the point decides how it behaves.
+unsafe extern "C" fn hit_test(
+    _window: *mut SDL_Window,
+    area: *const SDL_Point,
+    data: *mut c_void,
+) -> SDL_HitTestResult {
+    let regions = (*(data as *const RefCell<Vec<HitRegion>>)).borrow();
+    let (x, y) = ((*area).x, (*area).y);
+    match regions.iter().find(|region| region.contains(x, y)) {
+        Some(region) => region.hit.to_sdl(),
+        None => SDL_HitTestResult::SDL_HITTEST_NORMAL,
+    }
 }
 
 #[derive(Debug)]
 pub(super) enum Event {
     Quit,
+    Closed,
     Resized {
         width: i32,
         height: i32,
     },
     Exposed,
+    FocusGained,
+    FocusLost,
+    Minimized,
+    Maximized,
+    Restored,
+    MouseEntered,
+    MouseLeft,
+    DropBegin,
+    DropComplete,
     FileDropped {
         file: String,
         x: i32,
         y: i32,
     },
+    TextDropped {
+        text: String,
+        x: i32,
+        y: i32,
+    },
     KeyPressed {
         key: String,
     },
@@ -65,7 +255,32 @@ pub(super) enum Event {
         yrel: i32,
     },
     MouseWheel {
-        y: i32,
+        x: f32,
+        y: f32,
+    },
+    TouchPressed {
+        finger: i64,
+        x: f32,
+        y: f32,
+    },
+    TouchReleased {
+        finger: i64,
+        x: f32,
+        y: f32,
+    },
+    TouchMoved {
+        finger: i64,
+        x: f32,
+        y: f32,
+        dx: f32,
+        dy: f32,
+    },
+    Gesture {
+        pinch: f32,
+        rotation: f32,
+        x: f32,
+        y: f32,
+        fingers: u16,
     },
 }
 
@@ -97,150 +312,462 @@ impl Button {
     }
 }
 
-pub(super) struct Window {
-    event_pump: EventPump,
-    window: SdlWindow,
+fn global_mouse_position() -> (i32, i32) {
+    let (mut x, mut y) = (0, 0);
+    unsafe { SDL_GetGlobalMouseState(&mut x, &mut y) };
+    (x, y)
 }
 
-impl Window {
-    pub(super) fn init() -> Result<Self, ()> {
-        let context = sdl2::init().expect("Could not initialize SDL2");
-        let video = context
-            .video()
-            .expect("Could not initialize video subsystem");
-        let mut event_pump = context.event_pump().expect("Could not get event pump");
-        video.enable_screen_saver();
-        event_pump.enable_event(EventType::DropFile);
-        sdl2::hint::set("SDL_VIDEO_X11_NET_WM_BYPASS_COMPOSITOR", "0");
-        sdl2::hint::set("SDL_MOUSE_FOCUS_CLICKTHROUGH", "1");
-        let dm = video
-            .current_display_mode(0)
-            .expect("Could not get current display mode");
-        let window = video
-            .window(
-                "",
-                (f64::from(dm.w) * 0.8) as u32,
-                (f64::from(dm.h) * 0.8) as u32,
-            )
-            .position(0x1fff0000, 0x1fff0000)
-            .resizable()
-            .allow_highdpi()
-            .hidden()
-            .build()
-            .expect("Could not create window");
-        Ok(Self { event_pump, window })
-    }
-
-    fn context(&self) -> Sdl {
-        self.window.subsystem().sdl()
-    }
+/// The mouse wheel event of SDL 2.0.18, which added the fractional scroll
+/// amounts after the fields known to the bindings.
+#[repr(C)]
+struct PreciseWheelEvent {
+    type_: u32,
+    timestamp: u32,
+    window_id: u32,
+    which: u32,
+    x: i32,
+    y: i32,
+    direction: u32,
+    precise_x: f32,
+    precise_y: f32,
+}
 
-    fn event(&self) -> EventSubsystem {
-        self.context().event().unwrap()
-    }
+/// The timestamp and the fractional scroll amounts of the wheel events queued
+/// but not polled yet.
+static PRECISE_WHEEL: Lazy<Mutex<VecDeque<(u32, f32, f32)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
 
-    fn mouse(&self) -> MouseUtil {
-        self.context().mouse()
+/// Called by SDL with every event it queues, keeps the fractional scroll
+/// amounts which the bindings drop when converting the event.
+unsafe extern "C" fn watch_wheel(_data: *mut c_void, event: *mut SDL_Event) -> c_int {
+    if (*event).type_ == SDL_EventType::SDL_MOUSEWHEEL as u32 {
+        let wheel = &*(event as *const PreciseWheelEvent);
+        let mut queue = PRECISE_WHEEL.lock().unwrap();
+        if queue.len() == PENDING_LIMIT {
+            queue.pop_front();
+        }
+        queue.push_back((wheel.timestamp, wheel.precise_x, wheel.precise_y));
     }
+    0
+}
 
-    pub(super) fn clipboard(&self) -> ClipboardUtil {
-        self.window.subsystem().clipboard()
+/// Returns the fractional scroll amounts of the wheel event with the given
+/// timestamp, skipping the ones of events which were never polled.
+fn precise_wheel(timestamp: u32) -> Option<(f32, f32)> {
+    let mut queue = PRECISE_WHEEL.lock().unwrap();
+    while let Some(&(queued, x, y)) = queue.front() {
+        if queued > timestamp {
+            break;
+        }
+        queue.pop_front();
+        if queued == timestamp {
+            return Some((x, y));
+        }
     }
+    None
+}
 
-    pub(super) fn surface(&self) -> Result<WindowSurfaceRef, String> {
-        self.window.surface(&self.event_pump)
-    }
+/// How many events are kept for a window which does not poll them, the oldest
+/// ones are dropped past that.
+const PENDING_LIMIT: usize = 1024;
 
-    pub(super) fn show(&mut self) {
-        self.window.show()
-    }
+/// SDL has a single event queue for all the windows. Events polled by a window
+/// that target another one are kept here until that window polls them.
+struct EventQueue {
+    context: Sdl,
+    event_pump: EventPump,
+    main_id: u32,
+    pending: HashMap<u32, VecDeque<Event>>,
+}
 
-    pub(super) fn poll_event(&mut self) -> Option<Event> {
-        let event = self.event();
-        let mouse = self.mouse();
+impl EventQueue {
+    /// Returns the next event along with the id of the window it targets.
+    fn poll(&mut self) -> Option<(u32, Event)> {
+        let event = self.context.event().unwrap();
+        let mouse = self.context.mouse();
 
         loop {
-            match self.event_pump.poll_event() {
-                Option::None => return Option::None,
-                Some(SdlEvent::Quit { .. }) => {
-                    return Some(Event::Quit);
-                }
-                Some(SdlEvent::Window { win_event, .. }) => match win_event {
-                    WindowEvent::Resized(width, height) => {
-                        return Some(Event::Resized { width, height });
-                    }
-                    WindowEvent::Exposed => {
-                        return Some(Event::Exposed);
-                    }
+            let sdl_event = self.event_pump.poll_event()?;
+            let window_id = match sdl_event.get_window_id() {
+                Some(id) if self.pending.contains_key(&id) => id,
+                _ => self.main_id,
+            };
+            let event = match sdl_event {
+                SdlEvent::Quit { .. } => Event::Quit,
+                SdlEvent::Window { win_event, .. } => match win_event {
+                    WindowEvent::Resized(width, height) => Event::Resized { width, height },
+                    WindowEvent::Exposed => Event::Exposed,
                     WindowEvent::FocusGained => {
                         event.flush_event(EventType::KeyDown);
+                        Event::FocusGained
                     }
-                    _ => {}
+                    WindowEvent::FocusLost => Event::FocusLost,
+                    WindowEvent::Minimized => Event::Minimized,
+                    WindowEvent::Maximized => Event::Maximized,
+                    WindowEvent::Restored => Event::Restored,
+                    WindowEvent::Enter => Event::MouseEntered,
+                    WindowEvent::Leave => Event::MouseLeft,
+                    // Closing the last window also sends a quit event.
+                    WindowEvent::Close if window_id != self.main_id => Event::Closed,
+                    WindowEvent::Close if self.pending.len() > 1 => Event::Quit,
+                    _ => continue,
                 },
-                Some(SdlEvent::DropFile { filename, .. }) => {
-                    let mouse_state = self.event_pump.mouse_state();
-                    let (mx, my) = (mouse_state.x(), mouse_state.y());
-                    let (wx, wy) = self.window.position();
-
-                    return Some(Event::FileDropped {
+                SdlEvent::DropBegin { .. } => Event::DropBegin,
+                SdlEvent::DropComplete { .. } => Event::DropComplete,
+                // The mouse state is not updated while dragging, so the global
+                // position is used and made relative to the window once the
+                // event is delivered.
+                SdlEvent::DropFile { filename, .. } => {
+                    let (x, y) = global_mouse_position();
+                    Event::FileDropped {
                         file: filename,
-                        x: mx - wx,
-                        y: my - wy,
-                    });
-                }
-                Some(SdlEvent::KeyDown { keycode, .. }) => {
-                    return Some(Event::KeyPressed {
-                        key: keycode.unwrap().name().to_lowercase(),
-                    });
-                }
-                Some(SdlEvent::KeyUp { keycode, .. }) => {
-                    return Some(Event::KeyReleased {
-                        key: keycode.unwrap().name().to_lowercase(),
-                    });
+                        x,
+                        y,
+                    }
                 }
-                Some(SdlEvent::TextInput { text, .. }) => {
-                    return Some(Event::TextInput { text });
+                SdlEvent::DropText { filename, .. } => {
+                    let (x, y) = global_mouse_position();
+                    Event::TextDropped {
+                        text: filename,
+                        x,
+                        y,
+                    }
                 }
-                Some(SdlEvent::MouseButtonDown {
+                SdlEvent::KeyDown { keycode, .. } => Event::KeyPressed {
+                    key: keycode.unwrap().name().to_lowercase(),
+                },
+                SdlEvent::KeyUp { keycode, .. } => Event::KeyReleased {
+                    key: keycode.unwrap().name().to_lowercase(),
+                },
+                SdlEvent::TextInput { text, .. } => Event::TextInput { text },
+                SdlEvent::MouseButtonDown {
                     mouse_btn,
                     clicks,
                     x,
                     y,
                     ..
-                }) => {
+                } => {
                     if let MouseButton::Left = mouse_btn {
                         mouse.capture(true);
                     }
-                    return Some(Event::MousePressed {
+                    Event::MousePressed {
                         button: Button::from_sdl(mouse_btn),
                         x,
                         y,
                         clicks,
-                    });
+                    }
                 }
-                Some(SdlEvent::MouseButtonUp {
+                SdlEvent::MouseButtonUp {
                     mouse_btn, x, y, ..
-                }) => {
+                } => {
                     if let MouseButton::Left = mouse_btn {
                         mouse.capture(false);
                     }
-                    return Some(Event::MouseReleased {
+                    Event::MouseReleased {
                         button: Button::from_sdl(mouse_btn),
                         x,
                         y,
-                    });
+                    }
                 }
-                Some(SdlEvent::MouseMotion {
+                SdlEvent::MouseMotion {
                     x, y, xrel, yrel, ..
-                }) => {
-                    return Some(Event::MouseMoved { x, y, xrel, yrel });
+                } => Event::MouseMoved { x, y, xrel, yrel },
+                SdlEvent::MouseWheel {
+                    timestamp,
+                    x,
+                    y,
+                    direction,
+                    ..
+                } => {
+                    let (x, y) = precise_wheel(timestamp).unwrap_or((x as f32, y as f32));
+                    // Flipped values are made to follow the physical motion,
+                    // like the ones of an unflipped device.
+                    if let MouseWheelDirection::Flipped = direction {
+                        Event::MouseWheel { x: -x, y: -y }
+                    } else {
+                        Event::MouseWheel { x, y }
+                    }
                 }
-                Some(SdlEvent::MouseWheel { y, .. }) => {
-                    return Some(Event::MouseWheel { y });
+                // Touch positions are normalized to the 0..1 range of the
+                // touch device.
+                SdlEvent::FingerDown {
+                    finger_id, x, y, ..
+                } => Event::TouchPressed {
+                    finger: finger_id,
+                    x,
+                    y,
+                },
+                SdlEvent::FingerUp {
+                    finger_id, x, y, ..
+                } => Event::TouchReleased {
+                    finger: finger_id,
+                    x,
+                    y,
+                },
+                SdlEvent::FingerMotion {
+                    finger_id,
+                    x,
+                    y,
+                    dx,
+                    dy,
+                    ..
+                } => Event::TouchMoved {
+                    finger: finger_id,
+                    x,
+                    y,
+                    dx,
+                    dy,
+                },
+                SdlEvent::MultiGesture {
+                    d_theta,
+                    d_dist,
+                    x,
+                    y,
+                    num_fingers,
+                    ..
+                } => Event::Gesture {
+                    pinch: d_dist,
+                    rotation: d_theta,
+                    x,
+                    y,
+                    fingers: num_fingers,
+                },
+                _ => continue,
+            };
+            return Some((window_id, event));
+        }
+    }
+}
+
+const SYSTEM_CURSORS: [(&str, SystemCursor); 12] = [
+    ("arrow", SystemCursor::Arrow),
+    ("ibeam", SystemCursor::IBeam),
+    ("sizeh", SystemCursor::SizeWE),
+    ("sizev", SystemCursor::SizeNS),
+    ("hand", SystemCursor::Hand),
+    ("wait", SystemCursor::Wait),
+    ("waitarrow", SystemCursor::WaitArrow),
+    ("crosshair", SystemCursor::Crosshair),
+    ("no", SystemCursor::No),
+    ("sizeall", SystemCursor::SizeAll),
+    ("sizenwse", SystemCursor::SizeNWSE),
+    ("sizenesw", SystemCursor::SizeNESW),
+];
+
+/// The cursors created so far, by name. They are kept alive as SDL resets the
+/// cursor when the current one is freed.
+#[derive(Default)]
+struct Cursors {
+    cache: HashMap<String, Cursor>,
+    current: String,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(super) struct WindowGeometry {
+    pub(super) x: i32,
+    pub(super) y: i32,
+    pub(super) width: u32,
+    pub(super) height: u32,
+    pub(super) display: i32,
+}
+
+pub(super) struct DisplayInfo {
+    pub(super) index: i32,
+    pub(super) name: String,
+    pub(super) bounds: Rect,
+    /// The bounds without the areas reserved by the system, like taskbars.
+    pub(super) usable_bounds: Rect,
+    /// The diagonal, horizontal and vertical DPI, when they are known.
+    pub(super) dpi: Option<(f32, f32, f32)>,
+    /// The refresh rate in Hz, 0 when it is unknown.
+    pub(super) refresh_rate: i32,
+}
+
+/// The surface of a window, borrowed from it.
+pub(super) struct WindowSurface<'a> {
+    surface: &'a mut SurfaceRef,
+    window: &'a SdlWindow,
+}
+
+impl Deref for WindowSurface<'_> {
+    type Target = SurfaceRef;
+
+    fn deref(&self) -> &SurfaceRef {
+        self.surface
+    }
+}
+
+impl DerefMut for WindowSurface<'_> {
+    fn deref_mut(&mut self) -> &mut SurfaceRef {
+        self.surface
+    }
+}
+
+impl WindowSurface<'_> {
+    /// Copies the given areas of the surface to the screen.
+    pub(super) fn update_window_rects(&self, rects: &[Rect]) -> Result<(), String> {
+        let raw = unsafe {
+            SDL_UpdateWindowSurfaceRects(
+                self.window.raw(),
+                rects.as_ptr() as *const SDL_Rect,
+                rects.len() as c_int,
+            )
+        };
+        if raw != 0 {
+            return Err(sdl2::get_error());
+        }
+        Ok(())
+    }
+}
+
+pub(super) struct Window {
+    events: Rc<RefCell<EventQueue>>,
+    cursors: Rc<RefCell<Cursors>>,
+    window: SdlWindow,
+    // Shared with SDL, which keeps a pointer to it until the window is
+    // destroyed.
+    hit_regions: Rc<RefCell<Vec<HitRegion>>>,
+}
+
+impl Window {
+    /// Creates the main window, hidden until it is first drawn. A borderless
+    /// window is created without decorations rather than having them removed
+    /// once shown.
+    pub(super) fn init(borderless: bool) -> Result<Self, ()> {
+        let context = sdl2::init().expect("Could not initialize SDL2");
+        let video = context
+            .video()
+            .expect("Could not initialize video subsystem");
+        let mut event_pump = context.event_pump().expect("Could not get event pump");
+        video.enable_screen_saver();
+        event_pump.enable_event(EventType::DropFile);
+        event_pump.enable_event(EventType::DropText);
+        event_pump.enable_event(EventType::DropBegin);
+        event_pump.enable_event(EventType::DropComplete);
+        sdl2::hint::set("SDL_VIDEO_X11_NET_WM_BYPASS_COMPOSITOR", "0");
+        sdl2::hint::set("SDL_MOUSE_FOCUS_CLICKTHROUGH", "1");
+        let dm = video
+            .current_display_mode(0)
+            .expect("Could not get current display mode");
+        let mut builder = video.window(
+            "",
+            (f64::from(dm.w) * 0.8) as u32,
+            (f64::from(dm.h) * 0.8) as u32,
+        );
+        builder
+            .position(0x1fff0000, 0x1fff0000)
+            .resizable()
+            .allow_highdpi()
+            .hidden();
+        if borderless {
+            builder.borderless();
+        }
+        let window = builder.build().expect("Could not create window");
+        let mut pending = HashMap::new();
+        pending.insert(window.id(), VecDeque::new());
+        let version = sdl2::version::version();
+        if (version.major, version.minor, version.patch) >= (2, 0, 18) {
+            unsafe { SDL_AddEventWatch(Some(watch_wheel), ptr::null_mut()) };
+        }
+        let events = EventQueue {
+            context,
+            event_pump,
+            main_id: window.id(),
+            pending,
+        };
+        Ok(Self {
+            events: Rc::new(RefCell::new(events)),
+            cursors: Rc::new(RefCell::new(Cursors::default())),
+            window,
+            hit_regions: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+
+    /// Opens another window sharing the event queue of this one.
+    pub(super) fn open(
+        &self,
+        title: &str,
+        width: u32,
+        height: u32,
+        borderless: bool,
+    ) -> Result<Self, String> {
+        let video = self.window.subsystem();
+        let mut builder = video.window(title, width, height);
+        builder
+            .position_centered()
+            .resizable()
+            .allow_highdpi()
+            .hidden();
+        if borderless {
+            builder.borderless();
+        }
+        let window = builder.build().map_err(|error| error.to_string())?;
+        self.events
+            .borrow_mut()
+            .pending
+            .insert(window.id(), VecDeque::new());
+        Ok(Self {
+            events: Rc::clone(&self.events),
+            cursors: Rc::clone(&self.cursors),
+            window,
+            hit_regions: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+
+    pub(super) fn id(&self) -> u32 {
+        self.window.id()
+    }
+
+    /// Returns the surface drawn into by the renderer. The bindings require the
+    /// event pump to get it, which is mutably borrowed while polling, so SDL
+    /// is called directly instead.
+    pub(super) fn surface(&self) -> Result<WindowSurface<'_>, String> {
+        let surface = unsafe { SDL_GetWindowSurface(self.window.raw()) };
+        if surface.is_null() {
+            return Err(sdl2::get_error());
+        }
+        Ok(WindowSurface {
+            surface: unsafe { SurfaceRef::from_ll_mut(surface) },
+            window: &self.window,
+        })
+    }
+
+    pub(super) fn show(&mut self) {
+        self.window.show()
+    }
+
+    /// Returns the next event of this window. The events of the other windows
+    /// met on the way are kept until they poll them.
+    pub(super) fn poll_event(&mut self) -> Option<Event> {
+        let id = self.window.id();
+        let mut events = self.events.borrow_mut();
+        let mut event = events.pending.get_mut(&id).and_then(VecDeque::pop_front);
+        while event.is_none() {
+            let (window_id, next) = events.poll()?;
+            if window_id == id {
+                event = Some(next);
+            } else if let Some(queue) = events.pending.get_mut(&window_id) {
+                if queue.len() == PENDING_LIMIT {
+                    queue.pop_front();
                 }
-                _ => {}
+                queue.push_back(next);
             }
         }
+        let (wx, wy) = self.window.position();
+        match event {
+            Some(Event::FileDropped { file, x, y }) => Some(Event::FileDropped {
+                file,
+                x: x - wx,
+                y: y - wy,
+            }),
+            Some(Event::TextDropped { text, x, y }) => Some(Event::TextDropped {
+                text,
+                x: x - wx,
+                y: y - wy,
+            }),
+            event => event,
+        }
     }
 
     pub(super) fn set_title(&mut self, title: &str) {
@@ -257,12 +784,185 @@ impl Window {
                 FullscreenType::Off
             })
             .expect("Could not set fullscreen");
-        if matches!(mode, WindowMode::Normal) {
+        if matches!(mode, WindowMode::Normal | WindowMode::Borderless) {
+            self.window.set_bordered(matches!(mode, WindowMode::Normal));
             self.window.restore();
         }
         if matches!(mode, WindowMode::Maximized) {
             self.window.maximize();
         }
+        if matches!(mode, WindowMode::Minimized) {
+            self.window.minimize();
+        }
+    }
+
+    pub(super) fn mode(&self) -> WindowMode {
+        let flags = self.window.window_flags();
+        if self.window.fullscreen_state() != FullscreenType::Off {
+            WindowMode::Fullscreen
+        } else if flags & SDL_WindowFlags::SDL_WINDOW_MINIMIZED as c_uint != 0 {
+            WindowMode::Minimized
+        } else if flags & SDL_WindowFlags::SDL_WINDOW_MAXIMIZED as c_uint != 0 {
+            WindowMode::Maximized
+        } else if flags & SDL_WindowFlags::SDL_WINDOW_BORDERLESS as c_uint != 0 {
+            WindowMode::Borderless
+        } else {
+            WindowMode::Normal
+        }
+    }
+
+    /// Returns the position of the window relative to the display it is on,
+    /// along with its size and the index of that display.
+    pub(super) fn geometry(&self) -> WindowGeometry {
+        let display = self.window.display_index().unwrap_or(0);
+        let (dx, dy) = match self.window.subsystem().display_bounds(display) {
+            Ok(bounds) => (bounds.x(), bounds.y()),
+            Err(_) => (0, 0),
+        };
+        let (x, y) = self.window.position();
+        let (width, height) = self.window.size();
+        WindowGeometry {
+            x: x - dx,
+            y: y - dy,
+            width,
+            height,
+            display,
+        }
+    }
+
+    /// Moves and resizes the window. The position is relative to the display,
+    /// the first display is used if the display does not exist anymore.
+    pub(super) fn set_geometry(&mut self, geometry: WindowGeometry) -> Result<(), String> {
+        let video = self.window.subsystem();
+        let displays = video.num_video_displays()?;
+        let display = if (0..displays).contains(&geometry.display) {
+            geometry.display
+        } else {
+            0
+        };
+        let bounds = video.display_bounds(display)?;
+        self.window
+            .set_size(geometry.width, geometry.height)
+            .map_err(|error| error.to_string())?;
+        self.window.set_position(
+            WindowPos::Positioned(bounds.x() + geometry.x),
+            WindowPos::Positioned(bounds.y() + geometry.y),
+        );
+        Ok(())
+    }
+
+    /// Sets the cursor of all the windows, "none" hides it. Returns false if
+    /// there is no system or custom cursor with that name.
+    pub(super) fn set_cursor(&self, name: &str) -> bool {
+        let mut cursors = self.cursors.borrow_mut();
+        if cursors.current == name {
+            return true;
+        }
+        let mouse = self.window.subsystem().sdl().mouse();
+        if name == "none" {
+            mouse.show_cursor(false);
+        } else {
+            if !cursors.cache.contains_key(name) {
+                let cursor = SYSTEM_CURSORS
+                    .iter()
+                    .find(|(cursor_name, _)| *cursor_name == name)
+                    .and_then(|(_, cursor)| Cursor::from_system(*cursor).ok());
+                match cursor {
+                    Some(cursor) => cursors.cache.insert(name.to_string(), cursor),
+                    None => return false,
+                };
+            }
+            cursors.cache[name].set();
+            mouse.show_cursor(true);
+        }
+        cursors.current = name.to_string();
+        true
+    }
+
+    /// Loads a cursor from a BMP image so that it can be set by name, replacing
+    /// the cursor with the same name if any.
+    pub(super) fn create_cursor(
+        &self,
+        name: &str,
+        path: &Path,
+        hot_x: i32,
+        hot_y: i32,
+    ) -> Result<(), String> {
+        let surface = Surface::load_bmp(path)?;
+        let cursor = Cursor::from_surface(surface, hot_x, hot_y)?;
+        let mut cursors = self.cursors.borrow_mut();
+        if cursors.current == name {
+            cursor.set();
+        }
+        cursors.cache.insert(name.to_string(), cursor);
+        Ok(())
+    }
+
+    /// Replaces the regions used to move and resize the window, no region
+    /// disables hit-testing.
+    pub(super) fn set_hit_regions(&mut self, regions: Vec<HitRegion>) -> Result<(), String> {
+        let enable = !regions.is_empty();
+        *self.hit_regions.borrow_mut() = regions;
+        let (callback, data) = if enable {
+            (
+                Some(hit_test as _),
+                Rc::as_ptr(&self.hit_regions) as *mut c_void,
+            )
+        } else {
+            (None, ptr::null_mut())
+        };
+        if unsafe { SDL_SetWindowHitTest(self.window.raw(), callback, data) } != 0 {
+            return Err(sdl2::get_error());
+        }
+        Ok(())
+    }
+
+    /// Returns all the displays along with the index of the one the window is
+    /// on.
+    pub(super) fn displays(&self) -> Result<(Vec<DisplayInfo>, i32), String> {
+        let video = self.window.subsystem();
+        let displays = (0..video.num_video_displays()?)
+            .map(|index| {
+                Ok(DisplayInfo {
+                    index,
+                    name: video.display_name(index).unwrap_or_default(),
+                    bounds: video.display_bounds(index)?,
+                    usable_bounds: video.display_usable_bounds(index)?,
+                    dpi: video.display_dpi(index).ok(),
+                    refresh_rate: video.current_display_mode(index)?.refresh_rate,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok((displays, self.window.display_index()?))
+    }
+
+    pub(super) fn set_opacity(&mut self, opacity: f32) -> Result<(), String> {
+        self.window.set_opacity(opacity.clamp(0.0, 1.0))
+    }
+
+    pub(super) fn set_always_on_top(&mut self, on_top: bool) -> Result<(), String> {
+        let set_always_on_top = WINDOW_FUNCTIONS
+            .set_always_on_top
+            .ok_or_else(|| "Keeping windows on top is not supported".to_string())?;
+        let on_top = if on_top {
+            SDL_bool::SDL_TRUE
+        } else {
+            SDL_bool::SDL_FALSE
+        };
+        unsafe { set_always_on_top(self.window.raw(), on_top) };
+        Ok(())
+    }
+
+    /// Requests the attention of the user, usually by flashing the taskbar
+    /// entry of the window.
+    pub(super) fn flash(&mut self, mode: FlashMode) -> Result<(), String> {
+        let flash = WINDOW_FUNCTIONS
+            .flash
+            .ok_or_else(|| "Flashing windows is not supported".to_string())?;
+        if unsafe { flash(self.window.raw(), mode as c_int) } != 0 {
+            return Err(sdl2::get_error());
+        }
+        Ok(())
     }
 
     pub(super) fn has_focus(&self) -> bool {
@@ -271,7 +971,7 @@ impl Window {
     }
 
     pub(super) fn size(&self) -> (c_int, c_int) {
-        let surf = self.window.surface(&self.event_pump).unwrap();
+        let surf = self.surface().unwrap();
         (surf.width() as i32, surf.height() as i32)
     }
 
@@ -284,3 +984,9 @@ impl Window {
         1.0
     }
 }
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        self.events.borrow_mut().pending.remove(&self.window.id());
+    }
+}